@@ -0,0 +1,230 @@
+//! The in-memory container of the loaded chunks of a voxel world.
+use gs_schemas::chunk::Chunk;
+use gs_schemas::coordinates::{AbsBlockPos, AbsBlockRange, AbsChunkPos};
+use gs_schemas::voxeltypes::BlockId;
+use hashbrown::HashMap;
+use thiserror::Error;
+
+/// A world-level operation touched a chunk that is not loaded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+#[error("Chunk {0:?} is not loaded")]
+pub struct MissingChunkError(pub AbsChunkPos);
+
+/// A loaded chunk with its change tracking state.
+#[derive(Clone)]
+struct ChunkEntry {
+    chunk: Chunk,
+    dirty: bool,
+}
+
+/// The loaded chunks of a world, addressable by absolute block positions.
+///
+/// Every chunk has a dirty flag, set whenever the chunk is modified through the map,
+/// so that e.g. saving and meshing can find the chunks that changed since they last [took](Self::take_dirty) the flags.
+#[derive(Clone, Default)]
+pub struct ChunkMap {
+    chunks: HashMap<AbsChunkPos, ChunkEntry>,
+}
+
+impl ChunkMap {
+    /// Constructs an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of loaded chunks.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Checks if no chunks are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Checks if the chunk at the given position is loaded.
+    pub fn contains_chunk(&self, pos: AbsChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Read-only access to the chunk at the given position.
+    pub fn get_chunk(&self, pos: AbsChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos).map(|entry| &entry.chunk)
+    }
+
+    /// Mutable access to the chunk at the given position, which gets marked as dirty.
+    pub fn get_chunk_mut(&mut self, pos: AbsChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos).map(|entry| {
+            entry.dirty = true;
+            &mut entry.chunk
+        })
+    }
+
+    /// Inserts a chunk with the given dirty flag, returning the previously loaded chunk at that position along with its dirty flag.
+    /// Chunks loaded from disk are usually clean, while newly generated chunks are dirty.
+    pub fn insert_chunk(&mut self, pos: AbsChunkPos, chunk: Chunk, dirty: bool) -> Option<(Chunk, bool)> {
+        self.chunks
+            .insert(pos, ChunkEntry { chunk, dirty })
+            .map(|entry| (entry.chunk, entry.dirty))
+    }
+
+    /// Removes the chunk at the given position, returning it along with its dirty flag if it was loaded.
+    /// A dirty chunk has unsaved changes, so it usually needs to be saved before it's dropped.
+    pub fn remove_chunk(&mut self, pos: AbsChunkPos) -> Option<(Chunk, bool)> {
+        self.chunks.remove(&pos).map(|entry| (entry.chunk, entry.dirty))
+    }
+
+    /// Iterates over all the loaded chunks, in arbitrary order.
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (AbsChunkPos, &Chunk)> + '_ {
+        self.chunks.iter().map(|(&pos, entry)| (pos, &entry.chunk))
+    }
+
+    /// Gets the block at the given position, or [`None`] if its chunk is not loaded.
+    pub fn get_block(&self, pos: AbsBlockPos) -> Option<BlockId> {
+        let (chunk_pos, in_chunk) = pos.split_chunk();
+        self.get_chunk(chunk_pos).map(|chunk| chunk.get_block(in_chunk))
+    }
+
+    /// Sets the block at the given position, returning the old block, or [`None`] without any changes if its chunk is not loaded.
+    /// The chunk is only marked as dirty if the block actually changed.
+    pub fn set_block(&mut self, pos: AbsBlockPos, block: BlockId) -> Option<BlockId> {
+        let (chunk_pos, in_chunk) = pos.split_chunk();
+        let entry = self.chunks.get_mut(&chunk_pos)?;
+        let old = entry.chunk.set_block(in_chunk, block);
+        entry.dirty |= old != block;
+        Some(old)
+    }
+
+    /// Fills a cuboid of blocks spanning any number of chunks with the given block, marking all the touched chunks as dirty.
+    ///
+    /// If any of the chunks is not loaded, returns an error without modifying any chunks.
+    pub fn fill(&mut self, range: AbsBlockRange, block: BlockId) -> Result<(), MissingChunkError> {
+        if let Some(missing) = range.chunk_range().iter_xzy().find(|&pos| !self.contains_chunk(pos)) {
+            return Err(MissingChunkError(missing));
+        }
+        for (chunk_pos, in_chunk_range) in range.split_chunks() {
+            let entry = self
+                .chunks
+                .get_mut(&chunk_pos)
+                .expect("Checked that all chunks are loaded");
+            entry.chunk.fill(in_chunk_range, block);
+            entry.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Checks if the chunk at the given position is loaded and was modified since its dirty flag was last cleared.
+    pub fn is_dirty(&self, pos: AbsChunkPos) -> bool {
+        self.chunks.get(&pos).is_some_and(|entry| entry.dirty)
+    }
+
+    /// Sets the dirty flag of the chunk at the given position, returning whether the chunk is loaded.
+    pub fn mark_dirty(&mut self, pos: AbsChunkPos) -> bool {
+        self.chunks.get_mut(&pos).map(|entry| entry.dirty = true).is_some()
+    }
+
+    /// Iterates over the positions of all the dirty chunks, in arbitrary order.
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = AbsChunkPos> + '_ {
+        self.chunks.iter().filter(|(_, entry)| entry.dirty).map(|(&pos, _)| pos)
+    }
+
+    /// Clears all the dirty flags, returning the positions of the chunks that were dirty, in arbitrary order.
+    pub fn take_dirty(&mut self) -> Vec<AbsChunkPos> {
+        self.chunks
+            .iter_mut()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(&pos, entry)| {
+                entry.dirty = false;
+                pos
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gs_schemas::coordinates::{InChunkPos, CHUNK_DIM, CHUNK_DIM3Z};
+
+    use super::*;
+
+    #[test]
+    fn blocks_across_chunks() {
+        let mut map = ChunkMap::new();
+        let stone = BlockId::from_raw(2);
+        let dirt = BlockId::from_raw(3);
+        assert!(map.is_empty());
+        assert_eq!(map.get_block(AbsBlockPos::ZERO), None);
+        assert_eq!(map.set_block(AbsBlockPos::ZERO, stone), None);
+
+        for x in -1..=0 {
+            map.insert_chunk(AbsChunkPos::new(x, 0, 0), Chunk::new(), false);
+        }
+        assert_eq!(map.len(), 2);
+        assert!(map.dirty_chunks().next().is_none());
+
+        let negative = AbsBlockPos::new(-1, 5, 7);
+        assert_eq!(map.set_block(negative, stone), Some(BlockId::default()));
+        assert_eq!(map.get_block(negative), Some(stone));
+        assert_eq!(
+            map.get_chunk(AbsChunkPos::new(-1, 0, 0))
+                .unwrap()
+                .get_block(negative.split_chunk().1),
+            stone
+        );
+        assert!(map.is_dirty(AbsChunkPos::new(-1, 0, 0)));
+        assert!(!map.is_dirty(AbsChunkPos::ZERO));
+        assert_eq!(map.take_dirty(), [AbsChunkPos::new(-1, 0, 0)]);
+        assert!(!map.is_dirty(AbsChunkPos::new(-1, 0, 0)));
+        // Setting the same block doesn't dirty the chunk
+        assert_eq!(map.set_block(negative, stone), Some(stone));
+        assert!(map.take_dirty().is_empty());
+
+        let range = AbsBlockRange::from_corners(AbsBlockPos::new(-2, 0, 0), AbsBlockPos::new(1, 1, 1));
+        map.fill(range, dirt).unwrap();
+        for pos in range.iter_xzy() {
+            assert_eq!(map.get_block(pos), Some(dirt));
+        }
+        assert_eq!(map.get_block(AbsBlockPos::new(2, 0, 0)), Some(BlockId::default()));
+        let mut dirty = map.take_dirty();
+        dirty.sort_by_key(|pos| pos.x);
+        assert_eq!(dirty, [AbsChunkPos::new(-1, 0, 0), AbsChunkPos::ZERO]);
+
+        // Fills touching unloaded chunks are rejected as a whole
+        let too_big = AbsBlockRange::from_corners(AbsBlockPos::new(0, 0, 0), AbsBlockPos::new(CHUNK_DIM, 0, 0));
+        assert_eq!(
+            map.fill(too_big, stone),
+            Err(MissingChunkError(AbsChunkPos::new(1, 0, 0)))
+        );
+        assert_eq!(map.get_block(AbsBlockPos::ZERO), Some(dirt));
+        assert!(map.dirty_chunks().next().is_none());
+
+        assert!(map.mark_dirty(AbsChunkPos::ZERO));
+        assert!(!map.mark_dirty(AbsChunkPos::new(5, 0, 0)));
+        let (removed, dirty) = map.remove_chunk(AbsChunkPos::ZERO).unwrap();
+        assert!(dirty);
+        assert_eq!(
+            removed.iter_blocks().filter(|&(_, block)| block == dirt).count(),
+            2 * 2 * 2
+        );
+        assert!(!map.is_dirty(AbsChunkPos::ZERO));
+        assert_eq!(map.get_block(AbsBlockPos::ZERO), None);
+
+        let generated = Chunk::new_filled(stone);
+        assert!(map.insert_chunk(AbsChunkPos::ZERO, generated, true).is_none());
+        assert!(map.is_dirty(AbsChunkPos::ZERO));
+        assert_eq!(
+            map.iter_chunks()
+                .map(|(_, chunk)| chunk.iter_blocks().filter(|&(_, block)| block == stone).count())
+                .sum::<usize>(),
+            CHUNK_DIM3Z + 1
+        );
+        // Replacing a chunk returns the old one with its dirty flag
+        let (replaced, dirty) = map.insert_chunk(AbsChunkPos::ZERO, Chunk::new(), false).unwrap();
+        assert!(dirty);
+        assert_eq!(replaced.get_block(InChunkPos::ZERO), stone);
+        assert!(!map.is_dirty(AbsChunkPos::ZERO));
+        let (_, dirty) = map.remove_chunk(AbsChunkPos::new(-1, 0, 0)).unwrap();
+        assert!(!dirty);
+        assert!(map.remove_chunk(AbsChunkPos::new(-1, 0, 0)).is_none());
+    }
+}
@@ -1 +1,2 @@
-
+pub mod chunk_map;
+pub mod plugin;
@@ -0,0 +1,321 @@
+//! Exposes the voxel world to the ECS as a [`VoxelWorld`] resource, chunk entities and change events.
+use bevy::prelude::*;
+use gs_schemas::chunk::Chunk;
+use gs_schemas::coordinates::{AbsBlockPos, AbsBlockRange, AbsChunkPos};
+use gs_schemas::voxeltypes::BlockId;
+use hashbrown::HashMap;
+
+use crate::voxel::chunk_map::{ChunkMap, MissingChunkError};
+
+/// Adds the [`VoxelWorld`] resource, and keeps the chunk entities and events in sync with it.
+/// Only needs [`MinimalPlugins`], so it runs on headless servers.
+pub struct VoxelWorldPlugin;
+
+/// System sets of the [`VoxelWorldPlugin`].
+#[derive(SystemSet, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum VoxelWorldSystems {
+    /// Spawns and despawns the chunk entities and sends the events for the changes made to the [`VoxelWorld`], runs in [`PostUpdate`].
+    Sync,
+}
+
+/// Marks the entity representing a loaded chunk, with the position of that chunk.
+#[derive(Component, Copy, Clone, Eq, PartialEq, Hash, Debug, Deref)]
+pub struct VoxelChunk(pub AbsChunkPos);
+
+/// Sent when a chunk is loaded into the [`VoxelWorld`], after its entity is spawned.
+#[derive(Event, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ChunkLoaded {
+    /// The position of the chunk.
+    pub pos: AbsChunkPos,
+    /// The entity of the chunk.
+    pub entity: Entity,
+}
+
+/// Sent when a chunk is unloaded from the [`VoxelWorld`], after its entity is despawned.
+#[derive(Event, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ChunkUnloaded {
+    /// The position of the chunk.
+    pub pos: AbsChunkPos,
+    /// The despawned entity of the chunk.
+    pub entity: Entity,
+}
+
+/// Sent when a block in the [`VoxelWorld`] changes.
+#[derive(Event, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BlockChanged {
+    /// The position of the block.
+    pub pos: AbsBlockPos,
+    /// The block before the change.
+    pub old: BlockId,
+    /// The block after the change.
+    pub new: BlockId,
+}
+
+/// Sent when any number of blocks in a chunk of the [`VoxelWorld`] may have changed, without a [`BlockChanged`] for each block.
+#[derive(Event, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ChunkChanged {
+    /// The position of the chunk.
+    pub pos: AbsChunkPos,
+}
+
+/// A change to the [`VoxelWorld`] waiting to be synced to the ECS.
+#[derive(Copy, Clone, Debug)]
+enum PendingChange {
+    Loaded(AbsChunkPos),
+    Unloaded(AbsChunkPos),
+    BlockChanged(BlockChanged),
+    ChunkChanged(AbsChunkPos),
+}
+
+/// The loaded chunks of the world, with their entities.
+///
+/// Changes made through this resource are turned into entity updates and events in [`VoxelWorldSystems::Sync`],
+/// so chunk entities of newly loaded chunks only exist after that set runs.
+#[derive(Resource, Default)]
+pub struct VoxelWorld {
+    chunks: ChunkMap,
+    entities: HashMap<AbsChunkPos, Entity>,
+    pending: Vec<PendingChange>,
+}
+
+impl VoxelWorld {
+    /// Read-only access to the loaded chunks.
+    pub fn chunks(&self) -> &ChunkMap {
+        &self.chunks
+    }
+
+    /// The entity of the chunk at the given position, if it's loaded and synced.
+    pub fn chunk_entity(&self, pos: AbsChunkPos) -> Option<Entity> {
+        self.entities.get(&pos).copied()
+    }
+
+    /// Gets the block at the given position, or [`None`] if its chunk is not loaded.
+    pub fn get_block(&self, pos: AbsBlockPos) -> Option<BlockId> {
+        self.chunks.get_block(pos)
+    }
+
+    /// Sets the block at the given position, see [`ChunkMap::set_block`]. Sends [`BlockChanged`] if the block changed.
+    pub fn set_block(&mut self, pos: AbsBlockPos, block: BlockId) -> Option<BlockId> {
+        let old = self.chunks.set_block(pos, block)?;
+        if old != block {
+            self.pending
+                .push(PendingChange::BlockChanged(BlockChanged { pos, old, new: block }));
+        }
+        Some(old)
+    }
+
+    /// Fills a cuboid of blocks, see [`ChunkMap::fill`]. Sends [`ChunkChanged`] for every chunk the range touches.
+    pub fn fill(&mut self, range: AbsBlockRange, block: BlockId) -> Result<(), MissingChunkError> {
+        self.chunks.fill(range, block)?;
+        self.pending
+            .extend(range.chunk_range().iter_xzy().map(PendingChange::ChunkChanged));
+        Ok(())
+    }
+
+    /// Mutable access to the chunk at the given position, see [`ChunkMap::get_chunk_mut`].
+    /// Sends [`ChunkChanged`] if the chunk is loaded, as the changes made through the reference can't be tracked.
+    pub fn get_chunk_mut(&mut self, pos: AbsChunkPos) -> Option<&mut Chunk> {
+        let chunk = self.chunks.get_chunk_mut(pos)?;
+        self.pending.push(PendingChange::ChunkChanged(pos));
+        Some(chunk)
+    }
+
+    /// Loads a chunk, returning the replaced chunk along with its dirty flag, see [`ChunkMap::insert_chunk`].
+    /// Sends [`ChunkLoaded`], preceded by [`ChunkUnloaded`] if it replaces a loaded chunk.
+    pub fn load_chunk(&mut self, pos: AbsChunkPos, chunk: Chunk, dirty: bool) -> Option<(Chunk, bool)> {
+        let old = self.chunks.insert_chunk(pos, chunk, dirty);
+        if old.is_some() {
+            self.pending.push(PendingChange::Unloaded(pos));
+        }
+        self.pending.push(PendingChange::Loaded(pos));
+        old
+    }
+
+    /// Unloads the chunk at the given position, returning it along with its dirty flag if it was loaded, see [`ChunkMap::remove_chunk`].
+    /// Sends [`ChunkUnloaded`].
+    pub fn unload_chunk(&mut self, pos: AbsChunkPos) -> Option<(Chunk, bool)> {
+        let old = self.chunks.remove_chunk(pos)?;
+        self.pending.push(PendingChange::Unloaded(pos));
+        Some(old)
+    }
+
+    /// Clears all the dirty flags, see [`ChunkMap::take_dirty`].
+    pub fn take_dirty(&mut self) -> Vec<AbsChunkPos> {
+        self.chunks.take_dirty()
+    }
+}
+
+impl Plugin for VoxelWorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelWorld>()
+            .add_event::<ChunkLoaded>()
+            .add_event::<ChunkUnloaded>()
+            .add_event::<BlockChanged>()
+            .add_event::<ChunkChanged>()
+            .add_systems(PostUpdate, sync_voxel_world.in_set(VoxelWorldSystems::Sync));
+    }
+}
+
+fn sync_voxel_world(
+    mut commands: Commands,
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut loaded: EventWriter<ChunkLoaded>,
+    mut unloaded: EventWriter<ChunkUnloaded>,
+    mut changed: EventWriter<BlockChanged>,
+    mut chunk_changed: EventWriter<ChunkChanged>,
+) {
+    if voxel_world.pending.is_empty() {
+        return;
+    }
+    let voxel_world = &mut *voxel_world;
+    for change in voxel_world.pending.drain(..) {
+        match change {
+            PendingChange::Loaded(pos) => {
+                let entity = commands.spawn(VoxelChunk(pos)).id();
+                voxel_world.entities.insert(pos, entity);
+                loaded.send(ChunkLoaded { pos, entity });
+            }
+            PendingChange::Unloaded(pos) => {
+                if let Some(entity) = voxel_world.entities.remove(&pos) {
+                    commands.entity(entity).despawn();
+                    unloaded.send(ChunkUnloaded { pos, entity });
+                }
+            }
+            PendingChange::BlockChanged(event) => changed.send(event),
+            PendingChange::ChunkChanged(pos) => chunk_changed.send(ChunkChanged { pos }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::ecs::event::{Events, ManualEventReader};
+    use gs_schemas::coordinates::InChunkPos;
+
+    use super::*;
+
+    /// Reads the events sent since the last call with the same reader, independently of when the event buffers get swapped.
+    fn events<E: Event + Copy>(app: &App, reader: &mut ManualEventReader<E>) -> Vec<E> {
+        reader.read(app.world.resource::<Events<E>>()).copied().collect()
+    }
+
+    fn chunk_entities(app: &mut App) -> Vec<(Entity, AbsChunkPos)> {
+        let mut entities: Vec<_> = app
+            .world
+            .query::<(Entity, &VoxelChunk)>()
+            .iter(&app.world)
+            .map(|(entity, chunk)| (entity, chunk.0))
+            .collect();
+        entities.sort_by_key(|(_, pos)| pos.x);
+        entities
+    }
+
+    #[test]
+    fn headless_world_sync() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(VoxelWorldPlugin);
+        let mut loaded = ManualEventReader::<ChunkLoaded>::default();
+        let mut unloaded = ManualEventReader::<ChunkUnloaded>::default();
+        let mut changed = ManualEventReader::<BlockChanged>::default();
+        app.update();
+        assert!(chunk_entities(&mut app).is_empty());
+
+        let stone = BlockId::from_raw(2);
+        let mut voxel_world = app.world.resource_mut::<VoxelWorld>();
+        voxel_world.load_chunk(AbsChunkPos::ZERO, Chunk::new(), false);
+        voxel_world.load_chunk(AbsChunkPos::new(1, 0, 0), Chunk::new(), false);
+        assert_eq!(voxel_world.chunk_entity(AbsChunkPos::ZERO), None);
+        assert_eq!(
+            voxel_world.set_block(AbsBlockPos::new(1, 2, 3), stone),
+            Some(BlockId::default())
+        );
+        assert_eq!(voxel_world.set_block(AbsBlockPos::new(1, 2, 3), stone), Some(stone));
+        assert_eq!(voxel_world.set_block(AbsBlockPos::new(-1, 2, 3), stone), None);
+        app.update();
+
+        let entities = chunk_entities(&mut app);
+        let voxel_world = app.world.resource::<VoxelWorld>();
+        assert_eq!(entities.len(), 2);
+        for &(entity, pos) in entities.iter() {
+            assert_eq!(voxel_world.chunk_entity(pos), Some(entity));
+        }
+        assert_eq!(voxel_world.get_block(AbsBlockPos::new(1, 2, 3)), Some(stone));
+        assert_eq!(
+            events(&app, &mut loaded),
+            entities
+                .iter()
+                .map(|&(entity, pos)| ChunkLoaded { pos, entity })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            events(&app, &mut changed),
+            [BlockChanged {
+                pos: AbsBlockPos::new(1, 2, 3),
+                old: BlockId::default(),
+                new: stone,
+            }]
+        );
+        assert!(events(&app, &mut unloaded).is_empty());
+
+        let (origin_entity, _) = entities[0];
+        let mut voxel_world = app.world.resource_mut::<VoxelWorld>();
+        assert_eq!(voxel_world.take_dirty(), [AbsChunkPos::ZERO]);
+        // The dirty flag was taken before unloading
+        assert!(matches!(voxel_world.unload_chunk(AbsChunkPos::ZERO), Some((_, false))));
+        assert!(voxel_world.unload_chunk(AbsChunkPos::ZERO).is_none());
+        app.update();
+
+        assert_eq!(chunk_entities(&mut app), entities[1..]);
+        assert!(app.world.get_entity(origin_entity).is_none());
+        assert_eq!(app.world.resource::<VoxelWorld>().chunk_entity(AbsChunkPos::ZERO), None);
+        assert_eq!(
+            events(&app, &mut unloaded),
+            [ChunkUnloaded {
+                pos: AbsChunkPos::ZERO,
+                entity: origin_entity,
+            }]
+        );
+        assert!(events(&app, &mut loaded).is_empty());
+        assert!(events(&app, &mut changed).is_empty());
+    }
+
+    #[test]
+    fn range_and_chunk_changes() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(VoxelWorldPlugin);
+        let mut changed = ManualEventReader::<BlockChanged>::default();
+        let mut chunk_changed = ManualEventReader::<ChunkChanged>::default();
+        let stone = BlockId::from_raw(2);
+        let dirt = BlockId::from_raw(3);
+        let mut voxel_world = app.world.resource_mut::<VoxelWorld>();
+        voxel_world.load_chunk(AbsChunkPos::ZERO, Chunk::new(), false);
+        voxel_world.load_chunk(AbsChunkPos::new(1, 0, 0), Chunk::new(), false);
+        app.update();
+
+        let mut voxel_world = app.world.resource_mut::<VoxelWorld>();
+        let range = AbsBlockRange::from_corners(AbsBlockPos::new(30, 0, 0), AbsBlockPos::new(33, 1, 1));
+        voxel_world.fill(range, stone).unwrap();
+        let too_big = AbsBlockRange::from_corners(AbsBlockPos::new(-1, 0, 0), AbsBlockPos::new(1, 0, 0));
+        assert_eq!(
+            voxel_world.fill(too_big, dirt),
+            Err(MissingChunkError(AbsChunkPos::new(-1, 0, 0)))
+        );
+        voxel_world
+            .get_chunk_mut(AbsChunkPos::ZERO)
+            .unwrap()
+            .set_block(InChunkPos::ZERO, dirt);
+        assert!(voxel_world.get_chunk_mut(AbsChunkPos::new(2, 0, 0)).is_none());
+        app.update();
+
+        let voxel_world = app.world.resource::<VoxelWorld>();
+        for pos in range.iter_xzy() {
+            assert_eq!(voxel_world.get_block(pos), Some(stone));
+        }
+        assert_eq!(voxel_world.get_block(AbsBlockPos::ZERO), Some(dirt));
+        assert_eq!(
+            events(&app, &mut chunk_changed),
+            [AbsChunkPos::ZERO, AbsChunkPos::new(1, 0, 0), AbsChunkPos::ZERO].map(|pos| ChunkChanged { pos })
+        );
+        assert!(events(&app, &mut changed).is_empty());
+    }
+}
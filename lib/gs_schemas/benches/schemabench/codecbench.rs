@@ -0,0 +1,171 @@
+use std::num::NonZeroU32;
+
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use gs_schemas::chunk::{BlockLight, Chunk};
+use gs_schemas::chunk_codec::{
+    decode_with_dictionary, encode_with, ChunkCompression, ChunkDictionary, ChunkEncodeOptions,
+};
+use gs_schemas::chunk_storage::PaletteStorage;
+use gs_schemas::coordinates::{InChunkPos, CHUNK_DIM, CHUNK_DIM3Z};
+use gs_schemas::voxeltypes::BlockId;
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+use crate::chunkbench::random_paletted_chunk;
+
+const RANDOM_SEED: u64 = 0x2c91e5a7f03b6d84;
+const DICTIONARY_SAMPLES: u64 = 64;
+const DICTIONARY_SIZE: usize = 16 * 1024;
+
+const AIR: BlockId = BlockId::from_raw(0);
+const STONE: BlockId = BlockId::from_raw(1 << 32);
+const DIRT: BlockId = BlockId::from_raw(2 << 32);
+const GRASS: BlockId = BlockId::from_raw(3 << 32);
+const WATER: BlockId = BlockId::from_raw(4 << 32);
+const ORES: [BlockId; 3] = [
+    BlockId::from_raw(5 << 32),
+    BlockId::from_raw(6 << 32),
+    BlockId::from_raw(7 << 32),
+];
+
+/// Converts the random palette storage fixture used by the storage benchmarks into a chunk.
+pub fn random_chunk(block_types: u16) -> Chunk {
+    let storage = random_paletted_chunk(block_types);
+    let palette: Vec<BlockId> = storage.palette().iter().map(|&raw| BlockId::from_raw(raw)).collect();
+    let indices: Vec<u16> = storage.iter_indices().collect();
+    let blocks =
+        PaletteStorage::try_from_palette_and_indices(&palette, indices.as_slice().try_into().unwrap()).unwrap();
+    Chunk::from_parts(blocks, Default::default())
+}
+
+/// A chunk of rolling hills: stone with scattered ores, covered by dirt and grass, with water in the valleys and sky light above ground.
+pub fn terrain_chunk(seed: u64) -> Chunk {
+    let mut rng = Pcg64Mcg::seed_from_u64(RANDOM_SEED ^ seed);
+    let phase: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+    let base_height = rng.gen_range(8..20);
+    let sea_level = 12;
+    let mut chunk = Chunk::new();
+    for x in 0..CHUNK_DIM {
+        for z in 0..CHUNK_DIM {
+            let wave = (x as f32 * 0.21 + phase).sin() + (z as f32 * 0.13 + phase * 0.5).cos();
+            let height = base_height + (wave * 4.0) as i32;
+            for y in 0..CHUNK_DIM {
+                let block = if y < height - 3 {
+                    if rng.gen_ratio(1, 64) {
+                        *ORES.choose(&mut rng).unwrap()
+                    } else {
+                        STONE
+                    }
+                } else if y < height - 1 {
+                    DIRT
+                } else if y == height - 1 {
+                    if height <= sea_level {
+                        DIRT
+                    } else {
+                        GRASS
+                    }
+                } else if y < sea_level {
+                    WATER
+                } else {
+                    AIR
+                };
+                let pos = InChunkPos::try_new(x, y, z).unwrap();
+                chunk.set_block(pos, block);
+                if block == AIR {
+                    chunk.set_light(pos, BlockLight::MAX);
+                }
+            }
+        }
+    }
+    chunk
+}
+
+struct Fixture {
+    name: String,
+    chunk: Chunk,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let mut fixtures: Vec<Fixture> = [1, 8, 32, 128, 16384]
+        .into_iter()
+        .map(|block_types| Fixture {
+            name: format!("Random {block_types}"),
+            chunk: random_chunk(block_types),
+        })
+        .collect();
+    fixtures.push(Fixture {
+        name: "Terrain".to_owned(),
+        chunk: terrain_chunk(DICTIONARY_SAMPLES + 1),
+    });
+    fixtures
+}
+
+fn compressions() -> [(&'static str, ChunkCompression, bool); 6] {
+    [
+        ("Plain", ChunkCompression::None, false),
+        ("Run-length", ChunkCompression::RunLength, false),
+        ("Zstd", ChunkCompression::Zstd { level: 9 }, false),
+        ("Zstd+dict", ChunkCompression::Zstd { level: 9 }, true),
+        ("LZ4", ChunkCompression::Lz4, false),
+        ("LZ4+dict", ChunkCompression::Lz4, true),
+    ]
+}
+
+fn trained_dictionary() -> ChunkDictionary {
+    let samples: Vec<Chunk> = (0..DICTIONARY_SAMPLES).map(terrain_chunk).collect();
+    ChunkDictionary::train(NonZeroU32::new(1).unwrap(), samples.iter(), DICTIONARY_SIZE).unwrap()
+}
+
+fn chunk_encode_decode(c: &mut Criterion) {
+    let dictionary = trained_dictionary();
+    let fixtures = fixtures();
+
+    // Criterion only measures speed, so report the sizes separately
+    eprintln!("Encoded chunk sizes in bytes:");
+    for fixture in fixtures.iter() {
+        let sizes = compressions().map(|(name, compression, use_dictionary)| {
+            let options = ChunkEncodeOptions {
+                compression,
+                dictionary: use_dictionary.then_some(&dictionary),
+            };
+            format!("{name}: {}", encode_with(&fixture.chunk, options).len())
+        });
+        eprintln!("  {:<13} {}", fixture.name, sizes.join(", "));
+    }
+
+    let mut encode_group = c.benchmark_group("Encode chunk");
+    encode_group.throughput(Throughput::Elements(CHUNK_DIM3Z as u64));
+    for fixture in fixtures.iter() {
+        for (name, compression, use_dictionary) in compressions() {
+            let options = ChunkEncodeOptions {
+                compression,
+                dictionary: use_dictionary.then_some(&dictionary),
+            };
+            encode_group.bench_with_input(BenchmarkId::new(name, &fixture.name), &fixture.chunk, |b, chunk| {
+                b.iter(|| encode_with(black_box(chunk), options))
+            });
+        }
+    }
+    encode_group.finish();
+
+    let mut decode_group = c.benchmark_group("Decode chunk");
+    decode_group.throughput(Throughput::Elements(CHUNK_DIM3Z as u64));
+    for fixture in fixtures.iter() {
+        for (name, compression, use_dictionary) in compressions() {
+            let dictionary = use_dictionary.then_some(&dictionary);
+            let encoded = encode_with(
+                &fixture.chunk,
+                ChunkEncodeOptions {
+                    compression,
+                    dictionary,
+                },
+            );
+            decode_group.bench_with_input(BenchmarkId::new(name, &fixture.name), &encoded, |b, encoded| {
+                b.iter(|| decode_with_dictionary(black_box(encoded), dictionary).unwrap())
+            });
+        }
+    }
+    decode_group.finish();
+}
+
+criterion_group!(codec_benches, chunk_encode_decode);
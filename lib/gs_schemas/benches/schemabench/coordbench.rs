@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion};
+use gs_schemas::coordinates::AbsChunkPos;
+use rand::distributions::Uniform;
+use rand::prelude::*;
+use rand_pcg::Pcg64Mcg;
+
+const RANDOM_SEED: u64 = 0x5a4c2f0e9b17d3c1;
+const POSITION_COUNT: usize = 4096;
+
+pub fn random_chunk_positions(radius: i32) -> Vec<AbsChunkPos> {
+    let mut rng = Pcg64Mcg::seed_from_u64(RANDOM_SEED);
+    let dist = Uniform::new_inclusive(-radius, radius);
+    (0..POSITION_COUNT)
+        .map(|_| AbsChunkPos::new(dist.sample(&mut rng), dist.sample(&mut rng), dist.sample(&mut rng)))
+        .collect()
+}
+
+fn chunk_index_encode(c: &mut Criterion) {
+    let positions = random_chunk_positions(AbsChunkPos::INDEX_MAX_COORD);
+    c.bench_with_input(
+        BenchmarkId::new("Encode chunk index", "Morton"),
+        &positions,
+        |b, positions| {
+            b.iter(|| {
+                positions
+                    .iter()
+                    .map(|&pos| black_box(pos).try_as_morton_index().unwrap())
+                    .fold(0u64, u64::wrapping_add)
+            })
+        },
+    );
+    c.bench_with_input(
+        BenchmarkId::new("Encode chunk index", "Hilbert"),
+        &positions,
+        |b, positions| {
+            b.iter(|| {
+                positions
+                    .iter()
+                    .map(|&pos| black_box(pos).try_as_hilbert_index().unwrap())
+                    .fold(0u64, u64::wrapping_add)
+            })
+        },
+    );
+}
+
+fn chunk_index_decode(c: &mut Criterion) {
+    let positions = random_chunk_positions(AbsChunkPos::INDEX_MAX_COORD);
+    let morton: Vec<u64> = positions.iter().map(|p| p.try_as_morton_index().unwrap()).collect();
+    let hilbert: Vec<u64> = positions.iter().map(|p| p.try_as_hilbert_index().unwrap()).collect();
+    c.bench_with_input(
+        BenchmarkId::new("Decode chunk index", "Morton"),
+        &morton,
+        |b, indices| {
+            b.iter(|| {
+                indices
+                    .iter()
+                    .map(|&idx| AbsChunkPos::try_from_morton_index(black_box(idx)).unwrap())
+                    .fold(0i32, |acc, pos| acc.wrapping_add(pos.x))
+            })
+        },
+    );
+    c.bench_with_input(
+        BenchmarkId::new("Decode chunk index", "Hilbert"),
+        &hilbert,
+        |b, indices| {
+            b.iter(|| {
+                indices
+                    .iter()
+                    .map(|&idx| AbsChunkPos::try_from_hilbert_index(black_box(idx)).unwrap())
+                    .fold(0i32, |acc, pos| acc.wrapping_add(pos.x))
+            })
+        },
+    );
+}
+
+criterion_group!(coord_benches, chunk_index_encode, chunk_index_decode);
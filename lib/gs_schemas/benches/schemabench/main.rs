@@ -1,5 +1,11 @@
 use criterion::criterion_main;
 
 pub mod chunkbench;
+pub mod codecbench;
+pub mod coordbench;
 
-criterion_main!(chunkbench::chunk_benches);
+criterion_main!(
+    chunkbench::chunk_benches,
+    codecbench::codec_benches,
+    coordbench::coord_benches
+);
@@ -0,0 +1,241 @@
+//! Loading of data-driven [`BlockDefinition`]s from RON asset files.
+//!
+//! Every `.ron` file under the block directory (e.g. `assets/blocks`) defines a single block, all fields other than the name are optional:
+//! ```ron
+//! (
+//!     name: "gs:grass",
+//!     textures: (top: "gs:grass_top", bottom: "gs:dirt", sides: "gs:grass_side"),
+//!     tags: ["gs:natural"],
+//! )
+//! ```
+//! `Option` fields can be written without the `Some(...)` wrapper.
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+use ron::extensions::Extensions;
+use thiserror::Error;
+
+use crate::registry::{Registry, RegistryError, RegistryName};
+use crate::registry_tags::{collect_ron_files, TagDefinitions, TagEntry};
+use crate::voxeltypes::BlockDefinition;
+
+/// Possible errors from loading block definition files.
+#[derive(Debug, Error)]
+pub enum BlockLoadError {
+    /// The file or directory could not be read.
+    #[error("Could not read block data at {}: {source}", path.display())]
+    Io {
+        /// The path that failed to be read.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The file is not a valid block definition, the error includes the line and column.
+    #[error("Could not parse block file {}:{source}", path.display())]
+    Parse {
+        /// The path of the block file.
+        path: PathBuf,
+        /// The parsing error.
+        source: ron::error::SpannedError,
+    },
+    /// The same block name is defined in two files.
+    #[error("Block {name} is defined in both {} and {}", first.display(), second.display())]
+    DuplicateName {
+        /// The duplicated block name.
+        name: RegistryName,
+        /// The file loaded first.
+        first: PathBuf,
+        /// The file loaded second.
+        second: PathBuf,
+    },
+    /// The block could not be added to the registry, e.g. because a block of the same name was already registered.
+    #[error("Could not register the block from {}: {source}", path.display())]
+    Register {
+        /// The path of the block file.
+        path: PathBuf,
+        /// The registry error.
+        source: RegistryError,
+    },
+}
+
+/// Parses the RON source of a single block definition file.
+pub fn parse_block_definition(source: &str) -> Result<BlockDefinition, ron::error::SpannedError> {
+    ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_str(source)
+}
+
+/// Loads all the `.ron` block definition files under the given directory into the registry, returning the number of blocks loaded.
+///
+/// Files are registered in sorted path order, so that the assigned IDs are deterministic.
+/// The blocks are also added as [objects](TagEntry::Object) to the tags they list in [`BlockDefinition::tags`].
+/// All the files are parsed and checked for name conflicts before any block is registered, so such errors leave the registry unchanged.
+pub fn load_block_definitions(
+    dir: &Path,
+    registry: &mut Registry<BlockDefinition>,
+    tags: &mut TagDefinitions,
+) -> Result<usize, BlockLoadError> {
+    let mut files = Vec::new();
+    collect_ron_files(dir, &mut files).map_err(|(path, source)| BlockLoadError::Io { path, source })?;
+    files.sort();
+
+    let mut definitions = Vec::with_capacity(files.len());
+    let mut loaded_names: HashMap<RegistryName, usize> = HashMap::with_capacity(files.len());
+    for (idx, path) in files.iter().enumerate() {
+        let source = std::fs::read_to_string(path).map_err(|source| BlockLoadError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let definition = parse_block_definition(&source).map_err(|source| BlockLoadError::Parse {
+            path: path.clone(),
+            source,
+        })?;
+        if let Some(&first) = loaded_names.get(&definition.name) {
+            return Err(BlockLoadError::DuplicateName {
+                name: definition.name,
+                first: files[first].clone(),
+                second: path.clone(),
+            });
+        }
+        if registry.lookup_name_to_id(definition.name.as_ref()).is_some() {
+            return Err(BlockLoadError::Register {
+                path: path.clone(),
+                source: RegistryError::NameAlreadyExists { name: definition.name },
+            });
+        }
+        loaded_names.insert(definition.name.clone(), idx);
+        definitions.push(definition);
+    }
+
+    for (definition, path) in definitions.into_iter().zip(files.iter()) {
+        let name = definition.name.clone();
+        let block_tags = definition.tags.clone();
+        registry
+            .push_object(definition)
+            .map_err(|source| BlockLoadError::Register {
+                path: path.clone(),
+                source,
+            })?;
+        for tag in block_tags {
+            tags.add_entry(tag, TagEntry::Object(name.clone()));
+        }
+    }
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chunk::BlockLight;
+    use crate::coordinates::{Direction, Faces};
+    use crate::voxeltypes::{BlockShape, RenderMode};
+
+    fn name(name: &str) -> RegistryName {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_definitions() {
+        let minimal = parse_block_definition(r#"(name: "stone")"#).unwrap();
+        assert_eq!(minimal, BlockDefinition::new(name("gs:stone")));
+
+        let full = parse_block_definition(
+            r#"(
+                name: "mod:crystal",
+                shape: 3,
+                solid_sides: [NegY, PosY],
+                render_mode: Translucent,
+                light_emission: (10, 0, 31),
+                opacity: 4,
+                textures: (all: "mod:crystal", top: "mod:crystal_top", faces: { PosX: "mod:crystal_east" }),
+                tags: ["gs:glowing"],
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(full.name, name("mod:crystal"));
+        assert_eq!(full.shape, BlockShape::try_new(3).unwrap());
+        assert_eq!(full.solid_sides, Faces::from_iter([Direction::PosY, Direction::NegY]));
+        assert_eq!(full.render_mode, RenderMode::Translucent);
+        assert_eq!(full.light_emission, BlockLight::new(10, 0, 31));
+        assert_eq!(full.opacity, 4);
+        assert_eq!(full.textures.face(Direction::PosX), Some(&name("mod:crystal_east")));
+        assert_eq!(full.textures.face(Direction::PosY), Some(&name("mod:crystal_top")));
+        assert_eq!(full.textures.face(Direction::NegY), Some(&name("mod:crystal")));
+        assert_eq!(full.tags, vec![name("glowing")]);
+
+        let pretty = ron::ser::to_string_pretty(&full, Default::default()).unwrap();
+        assert_eq!(parse_block_definition(&pretty).unwrap(), full);
+    }
+
+    #[test]
+    fn parse_errors_have_positions() {
+        let position = |source: &str| parse_block_definition(source).unwrap_err().position;
+        let err_line = |source: &str| position(source).line;
+        assert_eq!(err_line("(\n    name: \"gs:Stone\",\n)"), 2);
+        assert_eq!(
+            err_line("(\n    name: \"stone\",\n    light_emission: (32, 0, 0),\n)"),
+            3
+        );
+        assert_eq!(err_line("(\n    name: \"stone\",\n    shape: 64,\n)"), 3);
+        assert_eq!(err_line("(\n    name: \"stone\",\n    colour: 1,\n)"), 3);
+        assert_eq!(err_line("(\n    shape: 1,\n)"), 3);
+    }
+
+    #[test]
+    fn load_shipped_assets() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets");
+        let mut registry: Registry<BlockDefinition> = Registry::default();
+        let mut tags = TagDefinitions::default();
+        let count = load_block_definitions(&assets.join("blocks"), &mut registry, &mut tags).unwrap();
+        assert!(count > 0);
+        assert_eq!(registry.len(), count);
+        tags.load_dir(&assets.join("tags/blocks")).unwrap();
+        let tags = tags.build(&registry).unwrap();
+
+        let (stone_id, stone) = registry.lookup_name_to_object(name("stone").as_ref()).unwrap();
+        assert_eq!(stone.render_mode, RenderMode::Opaque);
+        assert!(tags.contains(name("natural").as_ref(), stone_id));
+        let (log_id, _) = registry.lookup_name_to_object(name("oak_log").as_ref()).unwrap();
+        assert!(tags.contains(name("logs").as_ref(), log_id));
+        assert!(tags.contains(name("natural").as_ref(), log_id));
+        let (_, lamp) = registry.lookup_name_to_object(name("lamp").as_ref()).unwrap();
+        assert!(!lamp.light_emission.is_dark());
+    }
+
+    #[test]
+    fn load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("a.ron", r#"(name: "a", tags: ["t"])"#);
+        write("sub/b.ron", r#"(name: "b", tags: ["t"])"#);
+        let mut registry: Registry<BlockDefinition> = Registry::default();
+        let mut tags = TagDefinitions::default();
+        assert_eq!(load_block_definitions(dir.path(), &mut registry, &mut tags).unwrap(), 2);
+        assert_eq!(registry.lookup_name_to_id(name("a").as_ref()).unwrap().to_bits(), 1);
+        assert_eq!(tags.get(name("t").as_ref()).unwrap().len(), 2);
+
+        // Already registered
+        let mut tags = TagDefinitions::default();
+        let err = load_block_definitions(dir.path(), &mut registry, &mut tags).unwrap_err();
+        assert!(matches!(err, BlockLoadError::Register { .. }), "{err}");
+        assert!(tags.is_empty());
+
+        write("c.ron", r#"(name: "a")"#);
+        let mut registry: Registry<BlockDefinition> = Registry::default();
+        let err = load_block_definitions(dir.path(), &mut registry, &mut tags).unwrap_err();
+        assert!(
+            matches!(err, BlockLoadError::DuplicateName { ref name, .. } if name == &RegistryName::geosia("a")),
+            "{err}"
+        );
+        assert!(registry.is_empty());
+
+        write("c.ron", "(\n    name: \"c\",\n    render_mode: Glowing,\n)");
+        let err = load_block_definitions(dir.path(), &mut registry, &mut tags).unwrap_err();
+        assert!(err.to_string().contains("c.ron:3:"), "{err}");
+        assert!(registry.is_empty());
+    }
+}
@@ -1,18 +1,449 @@
 //! Representation of chunks of voxel data in the game.
+use std::fmt::{Debug, Formatter};
+
+use bevy_math::Vec3;
 use bytemuck::{Pod, Zeroable};
-use serde::{Deserialize, Serialize};
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::chunk_storage::{ArrayStorage, PaletteStorage};
-use crate::voxeltypes::BlockId;
+use crate::chunk_storage::{ArrayStorage, ChunkStorage, PaletteStorage};
+use crate::coordinates::{InChunkPos, InChunkRange};
+use crate::registry::Registry;
+use crate::voxeltypes::{BlockDefinition, BlockId, BlockIdValidationError, BlockIdValidationMode};
 
 /// RGB block light data (in a R5G5B5 format).
+///
+/// Deserialization rejects values with the unused highest bit set, like the [chunk codec](crate::chunk_codec) does.
 #[repr(transparent)]
-#[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Pod, Zeroable, Serialize, Deserialize)]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Pod, Zeroable, Serialize)]
 pub struct BlockLight(u16);
 
+/// Bit offset of the red channel in [`BlockLight`]
+const LIGHT_RED_SHIFT: u32 = 10;
+/// Bit offset of the green channel in [`BlockLight`]
+const LIGHT_GREEN_SHIFT: u32 = 5;
+/// Bit offset of the blue channel in [`BlockLight`]
+const LIGHT_BLUE_SHIFT: u32 = 0;
+/// Mask of a single channel in [`BlockLight`], before shifting
+const LIGHT_CHANNEL_MASK: u16 = 0b11111;
+
+impl BlockLight {
+    /// Maximum value of a single light channel.
+    pub const MAX_CHANNEL: u8 = LIGHT_CHANNEL_MASK as u8;
+    /// No light at all.
+    pub const ZERO: Self = Self(0);
+    /// Full-intensity white light.
+    pub const MAX: Self = Self::splat(Self::MAX_CHANNEL);
+
+    /// Constructs a light value from the given channel intensities, clamping them to [`Self::MAX_CHANNEL`].
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self(
+            (Self::clamp_channel(red) << LIGHT_RED_SHIFT)
+                | (Self::clamp_channel(green) << LIGHT_GREEN_SHIFT)
+                | (Self::clamp_channel(blue) << LIGHT_BLUE_SHIFT),
+        )
+    }
+
+    /// Same as `new(v, v, v)`
+    pub const fn splat(value: u8) -> Self {
+        Self::new(value, value, value)
+    }
+
+    /// Constructs a pure red light value.
+    pub const fn from_red(red: u8) -> Self {
+        Self::new(red, 0, 0)
+    }
+
+    /// Constructs a pure green light value.
+    pub const fn from_green(green: u8) -> Self {
+        Self::new(0, green, 0)
+    }
+
+    /// Constructs a pure blue light value.
+    pub const fn from_blue(blue: u8) -> Self {
+        Self::new(0, 0, blue)
+    }
+
+    /// Constructs a light value from the raw R5G5B5 bits, the highest bit is ignored.
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits & 0x7FFF)
+    }
+
+    /// The raw R5G5B5 bits.
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    const fn clamp_channel(value: u8) -> u16 {
+        if value > Self::MAX_CHANNEL {
+            LIGHT_CHANNEL_MASK
+        } else {
+            value as u16
+        }
+    }
+
+    const fn channel(self, shift: u32) -> u8 {
+        ((self.0 >> shift) & LIGHT_CHANNEL_MASK) as u8
+    }
+
+    /// Red channel intensity, in `0..=31`.
+    pub const fn red(self) -> u8 {
+        self.channel(LIGHT_RED_SHIFT)
+    }
+
+    /// Green channel intensity, in `0..=31`.
+    pub const fn green(self) -> u8 {
+        self.channel(LIGHT_GREEN_SHIFT)
+    }
+
+    /// Blue channel intensity, in `0..=31`.
+    pub const fn blue(self) -> u8 {
+        self.channel(LIGHT_BLUE_SHIFT)
+    }
+
+    /// Returns a copy with the red channel replaced.
+    pub const fn with_red(self, red: u8) -> Self {
+        Self::new(red, self.green(), self.blue())
+    }
+
+    /// Returns a copy with the green channel replaced.
+    pub const fn with_green(self, green: u8) -> Self {
+        Self::new(self.red(), green, self.blue())
+    }
+
+    /// Returns a copy with the blue channel replaced.
+    pub const fn with_blue(self, blue: u8) -> Self {
+        Self::new(self.red(), self.green(), blue)
+    }
+
+    /// Per-channel addition, clamping at [`Self::MAX_CHANNEL`].
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self::new(
+            self.red() + rhs.red(),
+            self.green() + rhs.green(),
+            self.blue() + rhs.blue(),
+        )
+    }
+
+    /// Per-channel subtraction, clamping at zero.
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self::new(
+            self.red().saturating_sub(rhs.red()),
+            self.green().saturating_sub(rhs.green()),
+            self.blue().saturating_sub(rhs.blue()),
+        )
+    }
+
+    /// Per-channel maximum, used for merging light coming from multiple sources during propagation.
+    pub const fn channel_max(self, rhs: Self) -> Self {
+        const fn max(a: u8, b: u8) -> u8 {
+            if a > b {
+                a
+            } else {
+                b
+            }
+        }
+        Self::new(
+            max(self.red(), rhs.red()),
+            max(self.green(), rhs.green()),
+            max(self.blue(), rhs.blue()),
+        )
+    }
+
+    /// Reduces every channel by one step, as happens when light propagates by one block.
+    pub const fn decay(self) -> Self {
+        self.saturating_sub(Self::splat(1))
+    }
+
+    /// Checks if all the channels are zero.
+    pub const fn is_dark(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Converts the light value to linear RGB intensities in the `0.0..=1.0` range, for use in shaders.
+    pub fn to_linear_vec3(self) -> Vec3 {
+        Vec3::new(self.red() as f32, self.green() as f32, self.blue() as f32) / (Self::MAX_CHANNEL as f32)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockLight {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The serialized representation of [`BlockLight`], before validation.
+        #[derive(Deserialize)]
+        #[serde(rename = "BlockLight")]
+        struct RawBlockLight(u16);
+
+        let RawBlockLight(bits) = RawBlockLight::deserialize(deserializer)?;
+        let light = BlockLight::from_bits(bits);
+        if light.to_bits() != bits {
+            return Err(D::Error::invalid_value(
+                Unexpected::Unsigned(bits.into()),
+                &"R5G5B5 light bits with the highest bit unset",
+            ));
+        }
+        Ok(light)
+    }
+}
+
 /// A 32³ grid of voxel data
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Chunk {
     blocks: PaletteStorage<BlockId>,
     light_level: ArrayStorage<BlockLight>,
 }
+
+impl Chunk {
+    /// Constructs a chunk filled with the default (empty) block and no light.
+    pub fn new() -> Self {
+        Self {
+            blocks: PaletteStorage::default(),
+            light_level: ArrayStorage::default(),
+        }
+    }
+
+    /// Constructs a chunk with every block set to the given block and no light.
+    pub fn new_filled(block: BlockId) -> Self {
+        Self {
+            blocks: PaletteStorage::new_filled(block),
+            light_level: ArrayStorage::default(),
+        }
+    }
+
+    /// Constructs a chunk out of the block and light level storages.
+    pub fn from_parts(blocks: PaletteStorage<BlockId>, light_levels: ArrayStorage<BlockLight>) -> Self {
+        Self {
+            blocks,
+            light_level: light_levels,
+        }
+    }
+
+    /// Read-only access to the block storage.
+    pub fn blocks(&self) -> &PaletteStorage<BlockId> {
+        &self.blocks
+    }
+
+    /// Read-only access to the light level storage.
+    pub fn light_levels(&self) -> &ArrayStorage<BlockLight> {
+        &self.light_level
+    }
+
+    /// Gets the block at the given position.
+    #[inline]
+    pub fn get_block(&self, position: InChunkPos) -> BlockId {
+        self.blocks.get_copy(position)
+    }
+
+    /// Sets the block at the given position, returning the old block.
+    #[inline]
+    pub fn set_block(&mut self, position: InChunkPos, block: BlockId) -> BlockId {
+        self.blocks.put(position, block)
+    }
+
+    /// Gets the light level at the given position.
+    #[inline]
+    pub fn get_light(&self, position: InChunkPos) -> BlockLight {
+        self.light_level.get_copy(position)
+    }
+
+    /// Sets the light level at the given position, returning the old light level.
+    #[inline]
+    pub fn set_light(&mut self, position: InChunkPos, light: BlockLight) -> BlockLight {
+        self.light_level.put(position, light)
+    }
+
+    /// Fills a cuboid of blocks with the given block.
+    pub fn fill(&mut self, range: InChunkRange, block: BlockId) {
+        self.blocks.fill(range, block);
+    }
+
+    /// Fills a cuboid of light levels with the given light level.
+    pub fn fill_light(&mut self, range: InChunkRange, light: BlockLight) {
+        self.light_level.fill(range, light);
+    }
+
+    /// Iterates over all the blocks paired with their coordinates, in XZY order.
+    pub fn iter_blocks(&self) -> impl Iterator<Item = (InChunkPos, BlockId)> + '_ {
+        self.blocks.iter_with_coords().map(|(pos, &block)| (pos, block))
+    }
+
+    /// Iterates over all the light levels paired with their coordinates, in XZY order.
+    pub fn iter_light(&self) -> impl Iterator<Item = (InChunkPos, BlockLight)> + '_ {
+        self.light_level.iter_with_coords().map(|(pos, &light)| (pos, light))
+    }
+
+    /// Approximate number of bytes used by this chunk, including heap allocations.
+    pub fn memory_usage(&self) -> usize {
+        self.blocks.memory_usage() + self.light_level.memory_usage()
+    }
+
+    /// Validates every block in the chunk against the registry, see [`BlockId::validate`].
+    /// Only the block palette is checked, so this is cheap even for full chunks.
+    ///
+    /// On error the chunk is left unmodified.
+    pub fn validate_blocks(
+        &mut self,
+        registry: &Registry<BlockDefinition>,
+        mode: BlockIdValidationMode,
+    ) -> Result<(), BlockIdValidationError> {
+        self.blocks.try_map_palette(|block| block.validate(registry, mode))
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for Chunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chunk")
+            .field("palette_size", &self.blocks.palette().len())
+            .field("light_singleton", &self.light_level.is_singleton())
+            .field("memory_bytes", &self.memory_usage())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::{Arbitrary, Gen};
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+
+    impl Arbitrary for BlockLight {
+        fn arbitrary(g: &mut Gen) -> Self {
+            BlockLight::from_bits(u16::arbitrary(g))
+        }
+    }
+
+    #[test]
+    fn chunk_validation() {
+        use crate::registry::RegistryName;
+
+        let mut registry: Registry<BlockDefinition> = Registry::default();
+        let stone = BlockDefinition::new(RegistryName::geosia("stone"));
+        let stone_id = registry.push_object(stone.clone()).unwrap();
+        let canonical = stone.block_id(stone_id);
+        let bad_bits = BlockId::builder(stone_id).build();
+
+        let mut chunk = Chunk::new();
+        chunk.set_block(InChunkPos::ZERO, canonical);
+        chunk.set_block(InChunkPos::ONE, bad_bits);
+        let original = chunk.clone();
+        assert!(chunk.validate_blocks(&registry, BlockIdValidationMode::Reject).is_err());
+        assert_eq!(chunk, original);
+        chunk
+            .validate_blocks(&registry, BlockIdValidationMode::Rewrite)
+            .unwrap();
+        assert_eq!(chunk.get_block(InChunkPos::ZERO), canonical);
+        assert_eq!(chunk.get_block(InChunkPos::ONE), canonical);
+        assert_eq!(chunk.get_block(InChunkPos::MAX), BlockId::default());
+        assert_eq!(chunk.blocks().palette().len(), 2);
+        chunk.validate_blocks(&registry, BlockIdValidationMode::Reject).unwrap();
+
+        // Unused palette entries of a filled chunk are not validated
+        let blocks =
+            PaletteStorage::try_from_palette_and_indices(&[canonical, bad_bits], &[0; crate::coordinates::CHUNK_DIM3Z])
+                .unwrap();
+        let mut filled = Chunk::from_parts(blocks, ArrayStorage::default());
+        filled
+            .validate_blocks(&registry, BlockIdValidationMode::Reject)
+            .unwrap();
+    }
+
+    #[test]
+    fn light_channels() {
+        let light = BlockLight::new(1, 2, 3);
+        assert_eq!((light.red(), light.green(), light.blue()), (1, 2, 3));
+        assert_eq!(BlockLight::MAX.to_bits(), 0x7FFF);
+        assert_eq!(BlockLight::new(200, 0, 0), BlockLight::from_red(31));
+        assert_eq!(BlockLight::from_green(5).with_blue(7), BlockLight::new(0, 5, 7));
+        assert_eq!(BlockLight::MAX.to_linear_vec3(), Vec3::ONE);
+        assert_eq!(BlockLight::ZERO.to_linear_vec3(), Vec3::ZERO);
+        assert_eq!(BlockLight::new(0, 1, 31).decay(), BlockLight::new(0, 0, 30));
+    }
+
+    #[quickcheck]
+    fn light_channel_roundtrip(r: u8, g: u8, b: u8) -> bool {
+        let light = BlockLight::new(r, g, b);
+        light.red() == r.min(31) && light.green() == g.min(31) && light.blue() == b.min(31)
+    }
+
+    #[quickcheck]
+    fn light_add_sub_saturate(a: BlockLight, b: BlockLight) -> bool {
+        let sum = a.saturating_add(b);
+        let diff = a.saturating_sub(b);
+        sum.red() == (a.red() + b.red()).min(31)
+            && sum.green() == (a.green() + b.green()).min(31)
+            && sum.blue() == (a.blue() + b.blue()).min(31)
+            && diff.red() == a.red().saturating_sub(b.red())
+            && diff.green() == a.green().saturating_sub(b.green())
+            && diff.blue() == a.blue().saturating_sub(b.blue())
+    }
+
+    #[quickcheck]
+    fn light_max_is_commutative_and_dominant(a: BlockLight, b: BlockLight) -> bool {
+        let m = a.channel_max(b);
+        m == b.channel_max(a)
+            && m.channel_max(a) == m
+            && m.red() >= a.red().max(b.red())
+            && m.green() >= a.green().max(b.green())
+            && m.blue() >= a.blue().max(b.blue())
+    }
+
+    #[quickcheck]
+    fn light_decay_reaches_dark(a: BlockLight) -> bool {
+        let mut light = a;
+        for _ in 0..BlockLight::MAX_CHANNEL {
+            light = light.decay();
+        }
+        light.is_dark()
+    }
+
+    #[test]
+    fn chunk_get_set() {
+        let air = BlockId::default();
+        let stone = BlockId::from_bits(1, 0, 0b111111, 0);
+        let dirt = BlockId::from_bits(2, 0, 0b111111, 0);
+        let mut chunk = Chunk::new_filled(stone);
+        assert!(chunk.iter_blocks().all(|(_, b)| b == stone));
+        assert_eq!(chunk.blocks().palette(), [stone]);
+        assert_eq!(chunk.memory_usage(), Chunk::new().memory_usage());
+
+        let pos = InChunkPos::try_new(1, 2, 3).unwrap();
+        assert_eq!(chunk.set_block(pos, dirt), stone);
+        assert_eq!(chunk.get_block(pos), dirt);
+        assert_eq!(chunk.get_block(InChunkPos::ZERO), stone);
+
+        chunk.fill(InChunkRange::from_corners(InChunkPos::ZERO, pos), air);
+        for (p, block) in chunk.iter_blocks() {
+            if p.x <= 1 && p.y <= 2 && p.z <= 3 {
+                assert_eq!(block, air);
+            } else {
+                assert_eq!(block, stone);
+            }
+        }
+
+        assert_eq!(chunk.get_light(pos), BlockLight::default());
+        assert_eq!(chunk.set_light(pos, BlockLight::MAX), BlockLight::default());
+        assert_eq!(chunk.get_light(pos), BlockLight::MAX);
+        assert!(format!("{chunk:?}").contains("palette_size"));
+
+        let ron = ron::to_string(&chunk).unwrap();
+        assert_eq!(ron::from_str::<Chunk>(&ron).unwrap(), chunk);
+        assert!(ron::from_str::<Chunk>("(blocks: (palette: [], indices: []), light_level: Singleton(0))").is_err());
+        let light_only = |light: &str| format!("(blocks: (palette: [(0)], indices: []), light_level: {light})");
+        let max = ron::from_str::<Chunk>(&light_only("Singleton((32767))")).unwrap();
+        assert_eq!(max.get_light(pos), BlockLight::MAX);
+        let err = ron::from_str::<Chunk>(&light_only("Singleton((32768))")).unwrap_err();
+        assert!(err.to_string().contains("32768"), "{err}");
+        assert!(ron::from_str::<BlockLight>("(65535)").is_err());
+        assert_eq!(
+            ron::from_str::<BlockLight>(&ron::to_string(&BlockLight::MAX).unwrap()),
+            Ok(BlockLight::MAX)
+        );
+    }
+}
@@ -0,0 +1,1041 @@
+//! Versioned, compact binary encoding of [`Chunk`]s, used for storing them on disk and sending them over the network.
+//!
+//! All integers are little-endian. Every encoded chunk starts with a header of:
+//! - `b"GSCK"` magic bytes
+//! - `u16` format version
+//! - `u8` [compression](ChunkCompression) kind: 0 for none, 1 for run-length, 2 for zstd, 3 for LZ4
+//! - `u32` ID of the [dictionary](ChunkDictionary) used for compression, 0 if none was used
+//!
+//! The uncompressed body is laid out as:
+//! - `u16` block palette length `N`, in `1..=32768`
+//! - `N` × `u64` raw [`BlockId`]s of the palette, without duplicates
+//! - `u8` index width `W`, 0 when `N` is 1, otherwise in `1..=16` with `2^W >= N`
+//! - 32768 × `W`-bit palette indices in XZY order, packed starting from the lowest bit of each byte (`4096 × W` bytes)
+//! - `u8` light storage kind: 0 for a single value for the whole chunk, 1 for a full array
+//! - `u16` raw [`BlockLight`], or 32768 × `u16` in XZY order
+//!
+//! The run-length body replaces the index width and the packed indices with runs of identical palette indices in XZY order,
+//! each run stored as a LEB128 varint length followed by a LEB128 varint palette index.
+//! The zstd and LZ4 bodies store the `u32` length of the uncompressed body, followed by the compressed packed body.
+//!
+//! Format version 1 had no compression kind and dictionary ID in the header.
+//! Older versions are still accepted by [`decode`], which upgrades them with [`CHUNK_MIGRATIONS`] first.
+use std::fmt::{Debug, Formatter};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+use crate::chunk::{BlockLight, Chunk};
+use crate::chunk_storage::{ArrayStorage, PaletteStorage, PaletteStorageError};
+use crate::coordinates::CHUNK_DIM3Z;
+use crate::migration::{MigrationError, CHUNK_MIGRATIONS};
+use crate::voxeltypes::BlockId;
+
+/// Magic bytes at the start of every encoded chunk.
+pub const CHUNK_MAGIC: [u8; 4] = *b"GSCK";
+/// The format version written by [`encode`].
+pub const CHUNK_FORMAT_VERSION: u16 = 2;
+
+const LIGHT_SINGLETON: u8 = 0;
+const LIGHT_ARRAY: u8 = 1;
+
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_RUN_LENGTH: u8 = 1;
+const COMPRESSION_ZSTD: u8 = 2;
+const COMPRESSION_LZ4: u8 = 3;
+
+/// Upper bound on the length of an uncompressed body, used to limit allocations when decompressing untrusted data.
+const MAX_BODY_LEN: usize = 2 + 8 * CHUNK_DIM3Z + 1 + 2 * CHUNK_DIM3Z + 1 + 2 * CHUNK_DIM3Z;
+
+/// Possible errors from decoding a chunk, the encoded data is never trusted.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum ChunkDecodeError {
+    /// The data doesn't start with [`CHUNK_MAGIC`].
+    #[error("Not an encoded chunk, the magic bytes don't match")]
+    BadMagic,
+    /// The data was encoded with a format version this code doesn't know about.
+    #[error("Unsupported chunk format version {0}")]
+    UnsupportedVersion(u16),
+    /// The compression kind is not known.
+    #[error("Unknown chunk compression kind {0}")]
+    UnknownCompression(u8),
+    /// The data has an older format version, and could not be upgraded to the current one.
+    #[error(transparent)]
+    Migration(#[from] MigrationError),
+    /// The chunk was compressed with a dictionary other than the one provided for decoding.
+    #[error("The chunk was compressed with dictionary {expected}, but dictionary {provided:?} was provided")]
+    DictionaryMismatch {
+        /// The ID of the dictionary used for compression.
+        expected: u32,
+        /// The ID of the dictionary provided for decoding, if any.
+        provided: Option<u32>,
+    },
+    /// The compressed data is corrupted.
+    #[error("Could not decompress the chunk data: {0}")]
+    Decompression(String),
+    /// The declared uncompressed length of the body is too large, or doesn't match the decompressed data.
+    #[error("Invalid uncompressed chunk body length {0}")]
+    InvalidBodyLength(u32),
+    /// The data ends in the middle of the chunk.
+    #[error("Unexpected end of the encoded chunk data")]
+    UnexpectedEnd,
+    /// There is extra data after the end of the chunk.
+    #[error("{0} unexpected bytes after the end of the encoded chunk")]
+    TrailingBytes(usize),
+    /// The palette index width is 0 or too large, or too small to index the whole palette.
+    #[error("Invalid palette index width of {width} bits for a palette of {palette_len} entries")]
+    InvalidIndexWidth {
+        /// The index width in bits.
+        width: u8,
+        /// The length of the palette.
+        palette_len: usize,
+    },
+    /// The run-length encoded indices don't add up to exactly a whole chunk, or contain an invalid varint.
+    #[error("Invalid run-length encoded palette indices")]
+    InvalidRuns,
+    /// The palette or indices break the block storage invariants.
+    #[error("Invalid block palette: {0}")]
+    InvalidPalette(#[from] PaletteStorageError),
+    /// The light storage kind is not known.
+    #[error("Unknown light storage kind {0}")]
+    InvalidLightKind(u8),
+    /// A light value has the unused high bit set.
+    #[error("Invalid light value {0:#06x}")]
+    InvalidLight(u16),
+}
+
+/// How the body of an encoded chunk is compressed, recorded in the header so that [`decode`] can handle any of them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum ChunkCompression {
+    /// Bit-packed palette indices without further compression.
+    #[default]
+    None,
+    /// Runs of identical palette indices in XZY order, cheap to produce and effective for terrain with large uniform areas.
+    RunLength,
+    /// zstd compression at the given level, see [`zstd::compression_level_range`] for the valid levels.
+    Zstd {
+        /// The compression level, higher levels are slower to compress but not to decompress.
+        level: i32,
+    },
+    /// LZ4 compression, fast enough for every chunk sent over the network.
+    Lz4,
+}
+
+impl ChunkCompression {
+    const fn tag(self) -> u8 {
+        match self {
+            ChunkCompression::None => COMPRESSION_NONE,
+            ChunkCompression::RunLength => COMPRESSION_RUN_LENGTH,
+            ChunkCompression::Zstd { .. } => COMPRESSION_ZSTD,
+            ChunkCompression::Lz4 => COMPRESSION_LZ4,
+        }
+    }
+
+    /// Whether this compression kind makes use of a [`ChunkDictionary`].
+    pub const fn uses_dictionary(self) -> bool {
+        matches!(self, ChunkCompression::Zstd { .. } | ChunkCompression::Lz4)
+    }
+}
+
+/// A dictionary shared by the encoding and decoding side, which improves zstd and LZ4 compression of small chunks.
+///
+/// The ID is recorded in the header of chunks compressed with the dictionary, so that decoding with a different dictionary
+/// is detected.
+///
+/// The zstd dictionary is digested once and reused for every chunk, so a dictionary should be kept around rather than
+/// recreated for each chunk.
+pub struct ChunkDictionary {
+    id: NonZeroU32,
+    data: Vec<u8>,
+    zstd_decoder: DecoderDictionary<'static>,
+    /// Digested encoder dictionaries depend on the compression level, so they are prepared on first use of each level.
+    zstd_encoders: Mutex<Vec<(i32, Arc<EncoderDictionary<'static>>)>>,
+}
+
+impl ChunkDictionary {
+    /// Wraps previously trained dictionary data, e.g. loaded from a file.
+    pub fn new(id: NonZeroU32, data: Vec<u8>) -> Self {
+        Self {
+            id,
+            zstd_decoder: DecoderDictionary::copy(&data),
+            zstd_encoders: Mutex::default(),
+            data,
+        }
+    }
+
+    /// Trains a zstd dictionary of at most `max_size` bytes on the encoded bodies of the given sample chunks.
+    /// This fails if there are too few samples to train on.
+    pub fn train<'c>(
+        id: NonZeroU32,
+        samples: impl IntoIterator<Item = &'c Chunk>,
+        max_size: usize,
+    ) -> std::io::Result<Self> {
+        let samples: Vec<Vec<u8>> = samples
+            .into_iter()
+            .map(|chunk| {
+                let mut body = Vec::new();
+                write_packed_body(chunk, &mut body);
+                body
+            })
+            .collect();
+        Ok(Self::new(id, zstd::dict::from_samples(&samples, max_size)?))
+    }
+
+    /// The ID recorded in the header of chunks compressed with this dictionary.
+    pub fn id(&self) -> NonZeroU32 {
+        self.id
+    }
+
+    /// The raw dictionary data, for storing it alongside the compressed chunks.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn zstd_encoder(&self, level: i32) -> Arc<EncoderDictionary<'static>> {
+        let mut encoders = self.zstd_encoders.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, encoder)) = encoders.iter().find(|(l, _)| *l == level) {
+            return encoder.clone();
+        }
+        let encoder = Arc::new(EncoderDictionary::copy(&self.data, level));
+        encoders.push((level, encoder.clone()));
+        encoder
+    }
+}
+
+impl Clone for ChunkDictionary {
+    fn clone(&self) -> Self {
+        Self::new(self.id, self.data.clone())
+    }
+}
+
+impl PartialEq for ChunkDictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.data == other.data
+    }
+}
+
+impl Eq for ChunkDictionary {}
+
+impl Debug for ChunkDictionary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkDictionary")
+            .field("id", &self.id)
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+/// Options for [`encode_with`], with presets for [disk storage](Self::DISK) and the [network](Self::NETWORK).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChunkEncodeOptions<'d> {
+    /// How to compress the chunk body.
+    pub compression: ChunkCompression,
+    /// The dictionary to compress with, ignored for the compression kinds which [don't use it](ChunkCompression::uses_dictionary).
+    /// The same dictionary has to be passed to [`decode_with_dictionary`].
+    pub dictionary: Option<&'d ChunkDictionary>,
+}
+
+impl ChunkEncodeOptions<'static> {
+    /// No compression, the same as [`encode`].
+    pub const PLAIN: Self = Self {
+        compression: ChunkCompression::None,
+        dictionary: None,
+    };
+    /// Favours size over encoding speed, for chunks saved to disk.
+    pub const DISK: Self = Self {
+        compression: ChunkCompression::Zstd { level: 9 },
+        dictionary: None,
+    };
+    /// Favours encoding speed, for chunks sent over the network.
+    pub const NETWORK: Self = Self {
+        compression: ChunkCompression::Lz4,
+        dictionary: None,
+    };
+}
+
+impl ChunkEncodeOptions<'_> {
+    /// Returns the same options with the given compression dictionary.
+    pub fn with_dictionary(self, dictionary: &ChunkDictionary) -> ChunkEncodeOptions<'_> {
+        ChunkEncodeOptions {
+            compression: self.compression,
+            dictionary: Some(dictionary),
+        }
+    }
+}
+
+/// Minimum number of bits needed to store indices into a palette of the given length, 0 for palettes of at most 1 entry.
+pub const fn palette_index_bits(palette_len: usize) -> u32 {
+    if palette_len <= 1 {
+        0
+    } else {
+        usize::BITS - (palette_len - 1).leading_zeros()
+    }
+}
+
+/// Encodes the chunk with the latest format version and no compression.
+pub fn encode(chunk: &Chunk) -> Vec<u8> {
+    encode_with(chunk, ChunkEncodeOptions::PLAIN)
+}
+
+/// Encodes the chunk with the latest format version and the given options.
+pub fn encode_with(chunk: &Chunk, options: ChunkEncodeOptions) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(chunk, options, &mut out);
+    out
+}
+
+/// Encodes the chunk with the latest format version and the given options, appending to the given buffer.
+///
+/// The encoding is canonical: unused palette entries are dropped and uniform light arrays are stored as a single value,
+/// so logically equal chunks encode to the same bytes with the same options.
+pub fn encode_into(chunk: &Chunk, options: ChunkEncodeOptions, out: &mut Vec<u8>) {
+    let compression = options.compression;
+    let dictionary = options.dictionary.filter(|_| compression.uses_dictionary());
+    out.extend_from_slice(&CHUNK_MAGIC);
+    out.extend_from_slice(&CHUNK_FORMAT_VERSION.to_le_bytes());
+    out.push(compression.tag());
+    out.extend_from_slice(&dictionary.map_or(0, |d| d.id().get()).to_le_bytes());
+
+    match compression {
+        ChunkCompression::None => write_packed_body(chunk, out),
+        ChunkCompression::RunLength => write_run_length_body(chunk, out),
+        ChunkCompression::Zstd { level } => {
+            let mut body = Vec::new();
+            write_packed_body(chunk, &mut body);
+            let encoder = dictionary.map(|d| d.zstd_encoder(level));
+            let compressed = match encoder.as_deref() {
+                Some(encoder) => zstd::bulk::Compressor::with_prepared_dictionary(encoder),
+                None => zstd::bulk::Compressor::new(level),
+            }
+            .and_then(|mut compressor| compressor.compress(&body))
+            .expect("In-memory zstd compression failed");
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&compressed);
+        }
+        ChunkCompression::Lz4 => {
+            let mut body = Vec::new();
+            write_packed_body(chunk, &mut body);
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            let dictionary = dictionary.map_or(&[][..], |d| d.data());
+            out.extend_from_slice(&lz4_flex::block::compress_with_dict(&body, dictionary));
+        }
+    }
+}
+
+/// Writes the palette entries used by the chunk, and returns the mapping from storage palette indices to encoded ones.
+fn write_palette(blocks: &PaletteStorage<BlockId>, out: &mut Vec<u8>) -> (Vec<u16>, usize) {
+    let palette = blocks.palette();
+    let mut used = vec![false; palette.len()];
+    for index in blocks.iter_indices() {
+        used[index as usize] = true;
+    }
+    let used_len = used.iter().filter(|&&used| used).count();
+    out.extend_from_slice(&(used_len as u16).to_le_bytes());
+    let mut remap = vec![0; palette.len()];
+    let mut next_index = 0;
+    for ((slot, value), used) in remap.iter_mut().zip(palette.iter()).zip(used) {
+        if used {
+            *slot = next_index;
+            next_index += 1;
+            out.extend_from_slice(&value.to_raw().to_le_bytes());
+        }
+    }
+    (remap, used_len)
+}
+
+fn write_packed_body(chunk: &Chunk, out: &mut Vec<u8>) {
+    let blocks = chunk.blocks();
+    let (remap, palette_len) = write_palette(blocks, out);
+    let width = palette_index_bits(palette_len);
+    out.push(width as u8);
+    if width > 0 {
+        let mut writer = BitWriter::new(out);
+        for index in blocks.iter_indices() {
+            writer.write(remap[index as usize], width);
+        }
+        writer.finish();
+    }
+    write_light(chunk.light_levels(), out);
+}
+
+fn write_run_length_body(chunk: &Chunk, out: &mut Vec<u8>) {
+    let blocks = chunk.blocks();
+    let (remap, _) = write_palette(blocks, out);
+    let mut indices = blocks.iter_indices().map(|index| remap[index as usize]);
+    let mut run_index = indices.next().unwrap_or_default();
+    let mut run_length: u32 = 1;
+    for index in indices {
+        if index == run_index {
+            run_length += 1;
+        } else {
+            write_varint(run_length, out);
+            write_varint(run_index as u32, out);
+            run_index = index;
+            run_length = 1;
+        }
+    }
+    write_varint(run_length, out);
+    write_varint(run_index as u32, out);
+    write_light(chunk.light_levels(), out);
+}
+
+fn write_light(light_levels: &ArrayStorage<BlockLight>, out: &mut Vec<u8>) {
+    let uniform_light = match light_levels {
+        ArrayStorage::Singleton(light) => Some(*light),
+        ArrayStorage::Array(lights) => lights.iter().all(|l| *l == lights[0]).then_some(lights[0]),
+    };
+    match (uniform_light, light_levels) {
+        (Some(light), _) => {
+            out.push(LIGHT_SINGLETON);
+            out.extend_from_slice(&light.to_bits().to_le_bytes());
+        }
+        (None, ArrayStorage::Array(lights)) => {
+            out.push(LIGHT_ARRAY);
+            out.reserve(lights.len() * 2);
+            for light in lights.iter() {
+                out.extend_from_slice(&light.to_bits().to_le_bytes());
+            }
+        }
+        (None, ArrayStorage::Singleton(_)) => unreachable!(),
+    }
+}
+
+/// Writes a LEB128 varint.
+fn write_varint(mut value: u32, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Decodes a chunk encoded with any supported format version and no dictionary, validating all the data.
+///
+/// Block IDs are not checked against the block registry, use [`Chunk::validate_blocks`] for that.
+pub fn decode(bytes: &[u8]) -> Result<Chunk, ChunkDecodeError> {
+    decode_with_dictionary(bytes, None)
+}
+
+/// Decodes a chunk encoded with any supported format version, validating all the data.
+/// The dictionary has to be the one the chunk was compressed with, if any.
+///
+/// Block IDs are not checked against the block registry, use [`Chunk::validate_blocks`] for that.
+pub fn decode_with_dictionary(bytes: &[u8], dictionary: Option<&ChunkDictionary>) -> Result<Chunk, ChunkDecodeError> {
+    let mut reader = Reader(bytes);
+    if reader.take(CHUNK_MAGIC.len())? != CHUNK_MAGIC {
+        return Err(ChunkDecodeError::BadMagic);
+    }
+    let upgraded;
+    let mut reader = match reader.u16()? {
+        version @ 1.. if version < CHUNK_FORMAT_VERSION => {
+            let mut blob = bytes.to_vec();
+            CHUNK_MIGRATIONS.migrate(&mut blob)?;
+            upgraded = blob;
+            Reader(&upgraded[CHUNK_MAGIC.len() + 2..])
+        }
+        CHUNK_FORMAT_VERSION => reader,
+        version => return Err(ChunkDecodeError::UnsupportedVersion(version)),
+    };
+    let compression = reader.u8()?;
+    let dictionary = match (reader.u32()?, dictionary) {
+        (0, _) => None,
+        (id, Some(dictionary)) if dictionary.id().get() == id => Some(dictionary),
+        (id, provided) => {
+            return Err(ChunkDecodeError::DictionaryMismatch {
+                expected: id,
+                provided: provided.map(|d| d.id().get()),
+            })
+        }
+    };
+    let chunk = match compression {
+        COMPRESSION_NONE => decode_packed_body(&mut reader)?,
+        COMPRESSION_RUN_LENGTH => decode_run_length_body(&mut reader)?,
+        COMPRESSION_ZSTD | COMPRESSION_LZ4 => {
+            let body_len = reader.u32()?;
+            if body_len as usize > MAX_BODY_LEN {
+                return Err(ChunkDecodeError::InvalidBodyLength(body_len));
+            }
+            let compressed = reader.take(reader.0.len())?;
+            let body = if compression == COMPRESSION_ZSTD {
+                match dictionary {
+                    Some(dictionary) => zstd::bulk::Decompressor::with_prepared_dictionary(&dictionary.zstd_decoder),
+                    None => zstd::bulk::Decompressor::new(),
+                }
+                .and_then(|mut decompressor| decompressor.decompress(compressed, body_len as usize))
+                .map_err(|e| ChunkDecodeError::Decompression(e.to_string()))?
+            } else {
+                let dictionary = dictionary.map_or(&[][..], |d| d.data());
+                lz4_flex::block::decompress_with_dict(compressed, body_len as usize, dictionary)
+                    .map_err(|e| ChunkDecodeError::Decompression(e.to_string()))?
+            };
+            if body.len() != body_len as usize {
+                return Err(ChunkDecodeError::InvalidBodyLength(body_len));
+            }
+            let mut body_reader = Reader(&body);
+            let chunk = decode_packed_body(&mut body_reader)?;
+            body_reader.finish()?;
+            chunk
+        }
+        kind => return Err(ChunkDecodeError::UnknownCompression(kind)),
+    };
+    reader.finish()?;
+    Ok(chunk)
+}
+
+fn read_palette(reader: &mut Reader) -> Result<Vec<BlockId>, ChunkDecodeError> {
+    let palette_len = reader.u16()? as usize;
+    if palette_len == 0 {
+        return Err(PaletteStorageError::EmptyPalette.into());
+    }
+    Ok(reader
+        .take(palette_len * 8)?
+        .chunks_exact(8)
+        .map(|raw| BlockId::from_raw(u64::from_le_bytes(raw.try_into().unwrap())))
+        .collect())
+}
+
+fn decode_packed_body(reader: &mut Reader) -> Result<Chunk, ChunkDecodeError> {
+    let palette = read_palette(reader)?;
+    let palette_len = palette.len();
+    let width = reader.u8()?;
+    let min_width = palette_index_bits(palette_len);
+    if width as u32 > 16 || width as u32 > 0 && (width as u32) < min_width || (width == 0) != (palette_len == 1) {
+        return Err(ChunkDecodeError::InvalidIndexWidth { width, palette_len });
+    }
+    let blocks = if width == 0 {
+        PaletteStorage::new_filled(palette[0])
+    } else {
+        let mut bit_reader = BitReader::new(reader.take(CHUNK_DIM3Z / 8 * width as usize)?);
+        let mut indices: Box<[u16; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+        for index in indices.iter_mut() {
+            *index = bit_reader.read(width as u32);
+        }
+        PaletteStorage::try_from_palette_and_indices(&palette, &indices)?
+    };
+    Ok(Chunk::from_parts(blocks, read_light(reader)?))
+}
+
+fn decode_run_length_body(reader: &mut Reader) -> Result<Chunk, ChunkDecodeError> {
+    let palette = read_palette(reader)?;
+    let mut indices: Box<[u16; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+    let mut filled = 0;
+    while filled < CHUNK_DIM3Z {
+        let run_length = reader.varint()? as usize;
+        let index = u16::try_from(reader.varint()?).map_err(|_| ChunkDecodeError::InvalidRuns)?;
+        if run_length == 0 || run_length > CHUNK_DIM3Z - filled {
+            return Err(ChunkDecodeError::InvalidRuns);
+        }
+        indices[filled..filled + run_length].fill(index);
+        filled += run_length;
+    }
+    let blocks = PaletteStorage::try_from_palette_and_indices(&palette, &indices)?;
+    Ok(Chunk::from_parts(blocks, read_light(reader)?))
+}
+
+fn read_light(reader: &mut Reader) -> Result<ArrayStorage<BlockLight>, ChunkDecodeError> {
+    let read_value = |reader: &mut Reader| {
+        let bits = reader.u16()?;
+        let light = BlockLight::from_bits(bits);
+        if light.to_bits() != bits {
+            return Err(ChunkDecodeError::InvalidLight(bits));
+        }
+        Ok(light)
+    };
+    match reader.u8()? {
+        LIGHT_SINGLETON => Ok(ArrayStorage::Singleton(read_value(reader)?)),
+        LIGHT_ARRAY => {
+            let mut lights: Box<[BlockLight; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+            for light in lights.iter_mut() {
+                *light = read_value(reader)?;
+            }
+            Ok(ArrayStorage::Array(lights))
+        }
+        kind => Err(ChunkDecodeError::InvalidLightKind(kind)),
+    }
+}
+
+/// A cursor over the encoded bytes.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ChunkDecodeError> {
+        if self.0.len() < len {
+            return Err(ChunkDecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChunkDecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChunkDecodeError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChunkDecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a LEB128 varint written by [`write_varint`], only used for index runs.
+    fn varint(&mut self) -> Result<u32, ChunkDecodeError> {
+        let mut value: u32 = 0;
+        for shift in (0..32).step_by(7) {
+            let byte = self.u8()?;
+            let bits = (byte & 0x7F) as u32;
+            // The 5th byte can only hold the top 4 bits of a u32
+            if shift == 28 && bits > 0x0F {
+                return Err(ChunkDecodeError::InvalidRuns);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ChunkDecodeError::InvalidRuns)
+    }
+
+    /// Checks that all the data was read.
+    fn finish(&self) -> Result<(), ChunkDecodeError> {
+        match self.0.len() {
+            0 => Ok(()),
+            n => Err(ChunkDecodeError::TrailingBytes(n)),
+        }
+    }
+}
+
+/// Packs values of up to 16 bits, starting from the lowest bit of each byte.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self {
+            out,
+            buffer: 0,
+            buffered_bits: 0,
+        }
+    }
+
+    #[inline]
+    fn write(&mut self, value: u16, width: u32) {
+        self.buffer |= (value as u32) << self.buffered_bits;
+        self.buffered_bits += width;
+        while self.buffered_bits >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.buffered_bits -= 8;
+        }
+    }
+
+    /// Writes out the last partial byte, padded with zero bits.
+    fn finish(self) {
+        if self.buffered_bits > 0 {
+            self.out.push(self.buffer as u8);
+        }
+    }
+}
+
+/// Unpacks values written by [`BitWriter`], reading zeroes past the end of the data.
+struct BitReader<'a> {
+    data: &'a [u8],
+    buffer: u32,
+    buffered_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            buffer: 0,
+            buffered_bits: 0,
+        }
+    }
+
+    #[inline]
+    fn read(&mut self, width: u32) -> u16 {
+        while self.buffered_bits < width {
+            let (&byte, rest) = self.data.split_first().unwrap_or((&0, &[]));
+            self.data = rest;
+            self.buffer |= (byte as u32) << self.buffered_bits;
+            self.buffered_bits += 8;
+        }
+        let value = self.buffer & ((1u32 << width) - 1);
+        self.buffer >>= width;
+        self.buffered_bits -= width;
+        value as u16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::{Arbitrary, Gen, TestResult};
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+    use crate::coordinates::{InChunkPos, InChunkRange};
+
+    #[derive(Clone, Debug)]
+    struct TestChunk(Chunk);
+
+    fn arbitrary_pos(g: &mut Gen) -> InChunkPos {
+        InChunkPos::try_from_index(usize::arbitrary(g) % CHUNK_DIM3Z).unwrap()
+    }
+
+    impl Arbitrary for TestChunk {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let mut chunk = Chunk::new_filled(BlockId::from_raw(u64::arbitrary(g)));
+            let distinct_blocks = *g.choose(&[0usize, 1, 2, 3, 17, 256, 257, 3000]).unwrap();
+            let blocks: Vec<BlockId> = (0..distinct_blocks)
+                .map(|_| BlockId::from_raw(u64::arbitrary(g)))
+                .collect();
+            for &block in blocks.iter() {
+                if bool::arbitrary(g) {
+                    chunk.fill(InChunkRange::from_corners(arbitrary_pos(g), arbitrary_pos(g)), block);
+                } else {
+                    chunk.set_block(arbitrary_pos(g), block);
+                }
+            }
+            if bool::arbitrary(g) {
+                chunk.fill_light(InChunkRange::WHOLE_CHUNK, BlockLight::arbitrary(g));
+                for _ in 0..u8::arbitrary(g) {
+                    chunk.set_light(arbitrary_pos(g), BlockLight::arbitrary(g));
+                }
+            }
+            Self(chunk)
+        }
+    }
+
+    impl Arbitrary for ChunkCompression {
+        fn arbitrary(g: &mut Gen) -> Self {
+            *g.choose(&[
+                ChunkCompression::None,
+                ChunkCompression::RunLength,
+                ChunkCompression::Zstd { level: 1 },
+                ChunkCompression::Lz4,
+            ])
+            .unwrap()
+        }
+    }
+
+    /// A chunk of stone, dirt and grass layers under air, with some scattered ores.
+    fn layered_chunk(seed: u64) -> Chunk {
+        let mut chunk = Chunk::new_filled(BlockId::from_raw(1));
+        let height = 8 + (seed % 16) as i32;
+        chunk.fill(
+            InChunkRange::from_corners(
+                InChunkPos::try_new(0, height, 0).unwrap(),
+                InChunkPos::try_new(31, 31, 31).unwrap(),
+            ),
+            BlockId::from_raw(0),
+        );
+        chunk.fill(
+            InChunkRange::from_corners(
+                InChunkPos::try_new(0, height - 3, 0).unwrap(),
+                InChunkPos::try_new(31, height - 1, 31).unwrap(),
+            ),
+            BlockId::from_raw(2),
+        );
+        for i in 0..32 {
+            chunk.set_block(
+                InChunkPos::try_new(i, height - 1, (i * 7) % 32).unwrap(),
+                BlockId::from_raw(3),
+            );
+            let ore = (seed as i32 * 31 + i * 17) % CHUNK_DIM3Z as i32;
+            let ore_pos = InChunkPos::try_from_index(ore as usize).unwrap();
+            if ore_pos.y < height - 3 {
+                chunk.set_block(ore_pos, BlockId::from_raw(4 + seed % 3));
+            }
+        }
+        chunk.fill_light(
+            InChunkRange::from_corners(
+                InChunkPos::try_new(0, height, 0).unwrap(),
+                InChunkPos::try_new(31, 31, 31).unwrap(),
+            ),
+            BlockLight::MAX,
+        );
+        chunk
+    }
+
+    #[quickcheck]
+    fn roundtrip(chunk: TestChunk, compression: ChunkCompression) -> bool {
+        let options = ChunkEncodeOptions {
+            compression,
+            dictionary: None,
+        };
+        let encoded = encode_with(&chunk.0, options);
+        let decoded = decode(&encoded).unwrap();
+        decoded == chunk.0 && encode_with(&decoded, options) == encoded
+    }
+
+    #[quickcheck]
+    fn corrupted_data_doesnt_panic(
+        chunk: TestChunk,
+        compression: ChunkCompression,
+        corruptions: Vec<(usize, u8)>,
+        truncate: usize,
+    ) -> TestResult {
+        let options = ChunkEncodeOptions {
+            compression,
+            dictionary: None,
+        };
+        let mut encoded = encode_with(&chunk.0, options);
+        if corruptions.is_empty() {
+            encoded.truncate(truncate % encoded.len());
+            return TestResult::from_bool(decode(&encoded).is_err());
+        }
+        let len = encoded.len();
+        for (pos, xor) in corruptions {
+            encoded[pos % len] ^= xor;
+        }
+        // Some corruptions produce valid chunks, this only checks that decoding doesn't panic
+        let _ = decode(&encoded);
+        TestResult::passed()
+    }
+
+    #[test]
+    fn index_widths() {
+        assert_eq!(palette_index_bits(1), 0);
+        assert_eq!(palette_index_bits(2), 1);
+        assert_eq!(palette_index_bits(3), 2);
+        assert_eq!(palette_index_bits(256), 8);
+        assert_eq!(palette_index_bits(257), 9);
+        assert_eq!(palette_index_bits(CHUNK_DIM3Z), 15);
+
+        let header_len = CHUNK_MAGIC.len() + 2 + 1 + 4 + 2;
+        let light_len = 1 + 2;
+        let empty = encode(&Chunk::new());
+        assert_eq!(empty.len(), header_len + 8 + 1 + light_len);
+        assert_eq!(&empty[..4], b"GSCK");
+        assert_eq!(u16::from_le_bytes([empty[4], empty[5]]), CHUNK_FORMAT_VERSION);
+
+        // Stale palette entries are not written
+        let stone = BlockId::from_raw(0x1_0000_0000);
+        let mut chunk = Chunk::new_filled(stone);
+        chunk.set_block(InChunkPos::ZERO, BlockId::default());
+        chunk.set_block(InChunkPos::ZERO, stone);
+        assert_eq!(encode(&chunk).len(), empty.len());
+
+        for distinct in [2usize, 3, 5, 200, 1000, CHUNK_DIM3Z] {
+            let mut chunk = Chunk::new();
+            for i in 0..CHUNK_DIM3Z {
+                let pos = InChunkPos::try_from_index(i).unwrap();
+                chunk.set_block(pos, BlockId::from_raw((i % distinct) as u64));
+            }
+            let width = palette_index_bits(distinct) as usize;
+            let encoded = encode(&chunk);
+            assert_eq!(encoded.len(), header_len + 8 * distinct + 1 + width * 4096 + light_len);
+            assert_eq!(decode(&encoded).unwrap(), chunk);
+        }
+
+        let mut lit = Chunk::new();
+        lit.set_light(InChunkPos::MAX, BlockLight::MAX);
+        assert_eq!(encode(&lit).len(), empty.len() - 2 + CHUNK_DIM3Z * 2);
+        lit.set_light(InChunkPos::MAX, BlockLight::ZERO);
+        assert_eq!(encode(&lit), empty);
+    }
+
+    #[test]
+    fn compression_kinds() {
+        let chunk = layered_chunk(5);
+        let plain = encode(&chunk);
+        for (compression, tag) in [
+            (ChunkCompression::None, 0),
+            (ChunkCompression::RunLength, 1),
+            (ChunkCompression::Zstd { level: 3 }, 2),
+            (ChunkCompression::Lz4, 3),
+        ] {
+            let encoded = encode_with(
+                &chunk,
+                ChunkEncodeOptions {
+                    compression,
+                    dictionary: None,
+                },
+            );
+            assert_eq!(encoded[6], tag);
+            assert_eq!(&encoded[7..11], &[0; 4]);
+            // Run-length encoding only applies to the block indices, not the light array
+            if compression.uses_dictionary() {
+                assert!(encoded.len() < plain.len() / 4, "{compression:?}: {}", encoded.len());
+            } else if compression == ChunkCompression::RunLength {
+                assert!(encoded.len() < plain.len() - 3 * 4096 / 2, "{}", encoded.len());
+            }
+            assert_eq!(decode(&encoded).unwrap(), chunk);
+        }
+
+        // Each run takes at least 2 bytes
+        let mut checkerboard = Chunk::new();
+        for i in (0..CHUNK_DIM3Z).step_by(2) {
+            checkerboard.set_block(InChunkPos::try_from_index(i).unwrap(), BlockId::from_raw(1));
+        }
+        let run_length = encode_with(
+            &checkerboard,
+            ChunkEncodeOptions {
+                compression: ChunkCompression::RunLength,
+                dictionary: None,
+            },
+        );
+        assert_eq!(run_length.len(), 13 + 16 + 2 * CHUNK_DIM3Z + 3);
+        assert_eq!(decode(&run_length).unwrap(), checkerboard);
+
+        // Version 1 has no compression and dictionary in the header
+        let mut v1 = plain.clone();
+        v1.drain(6..11);
+        v1[4] = 1;
+        assert_eq!(decode(&v1).unwrap(), chunk);
+    }
+
+    #[test]
+    fn dictionary_compression() {
+        let samples: Vec<Chunk> = (0..64).map(layered_chunk).collect();
+        let dictionary = ChunkDictionary::train(NonZeroU32::new(7).unwrap(), samples.iter(), 4096).unwrap();
+        assert_eq!(dictionary.id().get(), 7);
+        assert!(!dictionary.data().is_empty() && dictionary.data().len() <= 4096);
+        let other = ChunkDictionary::new(NonZeroU32::new(8).unwrap(), dictionary.data().to_vec());
+
+        let chunk = layered_chunk(100);
+        for options in [ChunkEncodeOptions::DISK, ChunkEncodeOptions::NETWORK] {
+            let without = encode_with(&chunk, options);
+            let with = encode_with(&chunk, options.with_dictionary(&dictionary));
+            assert_eq!(&with[7..11], &7u32.to_le_bytes());
+            assert!(with.len() <= without.len(), "{options:?}");
+            assert_eq!(decode_with_dictionary(&with, Some(&dictionary)).unwrap(), chunk);
+            assert_eq!(decode_with_dictionary(&without, Some(&dictionary)).unwrap(), chunk);
+            assert_eq!(
+                decode(&with),
+                Err(ChunkDecodeError::DictionaryMismatch {
+                    expected: 7,
+                    provided: None
+                })
+            );
+            assert_eq!(
+                decode_with_dictionary(&with, Some(&other)),
+                Err(ChunkDecodeError::DictionaryMismatch {
+                    expected: 7,
+                    provided: Some(8)
+                })
+            );
+        }
+
+        // Encoder dictionaries are prepared per compression level, clones prepare their own
+        let fast = ChunkEncodeOptions {
+            compression: ChunkCompression::Zstd { level: 1 },
+            dictionary: None,
+        };
+        let copy = dictionary.clone();
+        assert_eq!(copy, dictionary);
+        for (encoder, decoder) in [(&dictionary, &copy), (&copy, &dictionary)] {
+            let with = encode_with(&chunk, fast.with_dictionary(encoder));
+            assert_eq!(decode_with_dictionary(&with, Some(decoder)).unwrap(), chunk);
+        }
+
+        // The dictionary is not recorded for compression kinds that don't use it
+        let plain = encode_with(&chunk, ChunkEncodeOptions::PLAIN.with_dictionary(&dictionary));
+        assert_eq!(plain, encode(&chunk));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut chunk = Chunk::new();
+        chunk.set_block(InChunkPos::ZERO, BlockId::from_raw(7));
+        let encoded = encode(&chunk);
+        let with = |offset: usize, bytes: &[u8]| {
+            let mut data = encoded.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            decode(&data)
+        };
+        assert_eq!(with(0, b"GSCX"), Err(ChunkDecodeError::BadMagic));
+        assert_eq!(with(4, &[9, 0]), Err(ChunkDecodeError::UnsupportedVersion(9)));
+        assert_eq!(with(6, &[9]), Err(ChunkDecodeError::UnknownCompression(9)));
+        assert_eq!(
+            with(7, &[5]),
+            Err(ChunkDecodeError::DictionaryMismatch {
+                expected: 5,
+                provided: None
+            })
+        );
+        assert_eq!(
+            with(11, &[0, 0]),
+            Err(ChunkDecodeError::InvalidPalette(PaletteStorageError::EmptyPalette))
+        );
+        assert_eq!(decode(&encoded[..20]), Err(ChunkDecodeError::UnexpectedEnd));
+        // Duplicate palette entry
+        assert_eq!(
+            with(13, &7u64.to_le_bytes()),
+            Err(ChunkDecodeError::InvalidPalette(PaletteStorageError::DuplicateEntry(1)))
+        );
+        assert_eq!(
+            with(29, &[0]),
+            Err(ChunkDecodeError::InvalidIndexWidth {
+                width: 0,
+                palette_len: 2
+            })
+        );
+        assert_eq!(
+            with(29, &[17]),
+            Err(ChunkDecodeError::InvalidIndexWidth {
+                width: 17,
+                palette_len: 2
+            })
+        );
+        let light_kind = encoded.len() - 3;
+        assert_eq!(with(light_kind, &[2]), Err(ChunkDecodeError::InvalidLightKind(2)));
+        assert_eq!(
+            with(light_kind + 1, &[0, 0x80]),
+            Err(ChunkDecodeError::InvalidLight(0x8000))
+        );
+        let mut trailing = encoded.clone();
+        trailing.extend_from_slice(&[0, 0]);
+        assert_eq!(decode(&trailing), Err(ChunkDecodeError::TrailingBytes(2)));
+
+        // A palette of 3 entries with a 2-bit index of 3 is out of range
+        let mut chunk = Chunk::new();
+        chunk.set_block(InChunkPos::ZERO, BlockId::from_raw(1));
+        chunk.set_block(InChunkPos::ONE, BlockId::from_raw(2));
+        let mut encoded = encode(&chunk);
+        let indices_start = 13 + 3 * 8 + 1;
+        encoded[indices_start + 10] = 0b11;
+        assert!(matches!(
+            decode(&encoded),
+            Err(ChunkDecodeError::InvalidPalette(PaletteStorageError::IndexOutOfRange {
+                index: 3,
+                ..
+            }))
+        ));
+
+        // Runs must cover exactly the whole chunk
+        let run_length = |runs: &[u8]| {
+            let mut data = encode_with(
+                &Chunk::new(),
+                ChunkEncodeOptions {
+                    compression: ChunkCompression::RunLength,
+                    dictionary: None,
+                },
+            );
+            data.truncate(11 + 2 + 8);
+            data.extend_from_slice(runs);
+            data.extend_from_slice(&[LIGHT_SINGLETON, 0, 0]);
+            decode(&data)
+        };
+        assert_eq!(run_length(&[0x80, 0x80, 0x02, 0]), Ok(Chunk::new()));
+        assert_eq!(run_length(&[0x80, 0x80, 0x04, 0]), Err(ChunkDecodeError::InvalidRuns));
+        assert_eq!(run_length(&[0, 0]), Err(ChunkDecodeError::InvalidRuns));
+        assert_eq!(
+            run_length(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+            Err(ChunkDecodeError::InvalidRuns)
+        );
+        assert_eq!(run_length(&[0x80, 0x80, 0x02]), Err(ChunkDecodeError::UnexpectedEnd));
+
+        // Compressed bodies can't claim to be huge
+        let mut zstd = encode_with(&Chunk::new(), ChunkEncodeOptions::DISK);
+        zstd[11..15].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&zstd), Err(ChunkDecodeError::InvalidBodyLength(u32::MAX)));
+    }
+}
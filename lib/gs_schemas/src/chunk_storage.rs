@@ -7,7 +7,11 @@ use bitvec::prelude::*;
 use either::Either;
 use hashbrown::HashMap;
 use itertools::{iproduct, Itertools};
+use serde::de::Error as _;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::{smallvec, SmallVec};
+use thiserror::Error;
 
 use crate::coordinates::*;
 
@@ -40,7 +44,9 @@ pub trait ChunkStorage<DataType: ChunkDataType> {
 
 /// Chunk data compressed by storing a list of used values in a `palette` array and indices into that array for every chunk element.
 /// A special case for all data being of the same type has a very small memory footprint.
-#[derive(Clone, Eq, PartialEq)]
+///
+/// Equality compares the stored values, not the internal palette layout.
+#[derive(Clone)]
 pub struct PaletteStorage<DataType: ChunkDataType> {
     palette: SmallVec<[DataType; 16]>,
     /// Invariant: The length is 1, CHUNK_DIM3Z / 2 (u8 indices) or CHUNK_DIM3Z (u16 indices)
@@ -50,7 +56,9 @@ pub struct PaletteStorage<DataType: ChunkDataType> {
 }
 
 /// Simple XZY dense array storage for chunk data (with strides of X=1, Z=32, Y=32²).
-#[derive(Clone, Eq, PartialEq)]
+///
+/// Equality compares the stored values, so a singleton is equal to an array filled with the same value.
+#[derive(Clone)]
 pub enum ArrayStorage<T> {
     /// Single-element case for cases where every single chunk element is identical
     Singleton(T),
@@ -59,8 +67,12 @@ pub enum ArrayStorage<T> {
 }
 
 /// Storage for sparse chunk data, only allocating data for the data that's present at the cost of slower lookups and writes.
+/// Elements equal to the default value are not stored.
+#[derive(Clone, Eq, PartialEq)]
 pub struct SparseStorage<DataType: ChunkDataType> {
     data: HashMap<u16, DataType>,
+    /// The value returned for positions without an entry
+    default_value: DataType,
 }
 
 #[inline]
@@ -141,6 +153,30 @@ impl<'d> SafePaletteIndicesMut<'d> {
     }
 }
 
+/// Errors from constructing a [`PaletteStorage`] out of a palette and indices, e.g. when decoding untrusted data.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum PaletteStorageError {
+    /// The palette has no entries.
+    #[error("The palette is empty")]
+    EmptyPalette,
+    /// The palette has more entries than there are elements in a chunk.
+    #[error("The palette has {0} entries, more than the {CHUNK_DIM3Z} elements of a chunk")]
+    PaletteTooLarge(usize),
+    /// The palette contains the same value twice.
+    #[error("The palette entry at index {0} is a duplicate of an earlier entry")]
+    DuplicateEntry(usize),
+    /// An index points past the end of the palette.
+    #[error("Palette index {index} at {position:?} is out of range for a palette of {palette_len} entries")]
+    IndexOutOfRange {
+        /// The invalid index.
+        index: u16,
+        /// The position of the element with the invalid index.
+        position: InChunkPos,
+        /// The length of the palette.
+        palette_len: usize,
+    },
+}
+
 /// Maximum number of elements in the palette that uses [`u8`] storage
 const PAL_BYTE_CUTOFF: usize = 255;
 /// Length of the data array when using u8-typed data
@@ -176,6 +212,74 @@ impl<DataType: ChunkDataType + Copy> PaletteStorage<DataType> {
         self.iter().enumerate_xzy()
     }
 
+    /// Iterates over the [palette](Self::palette) index of every element, in XZY order.
+    pub fn iter_indices(&self) -> impl Iterator<Item = u16> + '_ {
+        match self.data() {
+            SafePaletteIndices::Singleton => Either::Left(std::iter::repeat(0).take(CHUNK_DIM3Z)),
+            SafePaletteIndices::U8(indices) => Either::Right(Either::Left(indices.iter().map(|&idx| idx as u16))),
+            SafePaletteIndices::U16(indices) => Either::Right(Either::Right(indices.iter().copied())),
+        }
+    }
+
+    /// The list of values referenced by the chunk data, can contain unused entries until the next palette GC.
+    pub fn palette(&self) -> &[DataType] {
+        &self.palette
+    }
+
+    /// Rewrites every palette entry with the given function, without touching the per-block indices.
+    /// If the function fails for any entry, the logical contents of the storage are left unmodified and the error is returned.
+    ///
+    /// Unused entries are garbage collected first, so the function is only called for values present in the chunk.
+    /// Entries mapped to identical values are merged, so the palette stays free of duplicates.
+    pub fn try_map_palette<E>(&mut self, mut f: impl FnMut(&DataType) -> Result<DataType, E>) -> Result<(), E>
+    where
+        DataType: Eq,
+    {
+        self.palette_gc(None);
+        let new_palette = self
+            .palette
+            .iter()
+            .map(&mut f)
+            .collect::<Result<SmallVec<[DataType; 16]>, E>>()?;
+        let mut first_indices: HashMap<DataType, u16> = HashMap::with_capacity(new_palette.len());
+        let first_occurrence: Vec<u16> = new_palette
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| *first_indices.entry(v).or_insert(i as u16))
+            .collect();
+        self.palette = new_palette;
+        if first_occurrence
+            .iter()
+            .enumerate()
+            .all(|(i, &first)| i == first as usize)
+        {
+            return Ok(());
+        }
+        match self.data_mut() {
+            SafePaletteIndicesMut::Singleton => {}
+            SafePaletteIndicesMut::U8(indices) => indices
+                .iter_mut()
+                .for_each(|idx| *idx = first_occurrence[*idx as usize] as u8),
+            SafePaletteIndicesMut::U16(indices) => indices
+                .iter_mut()
+                .for_each(|idx| *idx = first_occurrence[*idx as usize]),
+        }
+        self.palette_gc(None);
+        Ok(())
+    }
+
+    /// Approximate number of bytes used by this storage, including heap allocations.
+    pub fn memory_usage(&self) -> usize {
+        let mut bytes = std::mem::size_of::<Self>();
+        if self.palette.spilled() {
+            bytes += self.palette.capacity() * std::mem::size_of::<DataType>();
+        }
+        if self.data_storage.spilled() {
+            bytes += self.data_storage.capacity() * std::mem::size_of::<u16>();
+        }
+        bytes
+    }
+
     /// Garbage collect unused palette entries, compacting the chunk data.
     #[cold]
     fn palette_gc(&mut self, ignored_coord: Option<InChunkPos>) {
@@ -316,6 +420,81 @@ impl<DataType: ChunkDataType + Copy> Default for PaletteStorage<DataType> {
         }
     }
 }
+impl<DataType: ChunkDataType + Copy + Eq> PaletteStorage<DataType> {
+    /// Constructs a storage with every element set to the given value.
+    pub fn new_filled(value: DataType) -> Self {
+        Self {
+            palette: smallvec![value],
+            data_storage: smallvec![0],
+            last_gc_palette_len: 0,
+        }
+    }
+
+    /// Constructs a storage from a palette of unique values and the palette index of every element in XZY order,
+    /// validating that the indices are in range. Unused palette entries are kept until the next palette GC,
+    /// except when all the indices are 0, which results in a singleton storage of just the first entry.
+    pub fn try_from_palette_and_indices(
+        palette: &[DataType],
+        indices: &[u16; CHUNK_DIM3Z],
+    ) -> Result<Self, PaletteStorageError> {
+        if palette.is_empty() {
+            return Err(PaletteStorageError::EmptyPalette);
+        }
+        if palette.len() > CHUNK_DIM3Z {
+            return Err(PaletteStorageError::PaletteTooLarge(palette.len()));
+        }
+        let mut seen: HashMap<&DataType, ()> = HashMap::with_capacity(palette.len());
+        for (idx, value) in palette.iter().enumerate() {
+            if seen.insert(value, ()).is_some() {
+                return Err(PaletteStorageError::DuplicateEntry(idx));
+            }
+        }
+        if let Some((i, &index)) = indices
+            .iter()
+            .enumerate()
+            .find(|(_, &index)| index as usize >= palette.len())
+        {
+            return Err(PaletteStorageError::IndexOutOfRange {
+                index,
+                position: InChunkPos::try_from_index(i).unwrap(),
+                palette_len: palette.len(),
+            });
+        }
+
+        if indices.iter().all(|&index| index == 0) {
+            // Singleton storages skip the palette GC, so they can't keep unused entries
+            return Ok(Self::new_filled(palette[0]));
+        }
+        let mut storage = Self {
+            palette: SmallVec::from_slice(palette),
+            data_storage: smallvec![0],
+            last_gc_palette_len: 0,
+        };
+        storage.upgrade_storage();
+        if palette.len() > u8::MAX as usize + 1 {
+            storage.upgrade_storage();
+        }
+        match storage.data_mut() {
+            SafePaletteIndicesMut::Singleton => unreachable!(),
+            SafePaletteIndicesMut::U8(data) => {
+                for (out, &index) in data.iter_mut().zip(indices.iter()) {
+                    *out = index as u8;
+                }
+            }
+            SafePaletteIndicesMut::U16(data) => data.copy_from_slice(indices),
+        }
+        Ok(storage)
+    }
+}
+
+/// Compares the stored values, not the representation: the palette order, stale palette entries and the index width
+/// depend on the history of modifications, so equal chunks can have different palettes.
+impl<DataType: ChunkDataType + Copy> PartialEq for PaletteStorage<DataType> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.palette == other.palette && self.data_storage == other.data_storage) || self.iter().eq(other.iter())
+    }
+}
+impl<DataType: ChunkDataType + Copy + Eq> Eq for PaletteStorage<DataType> {}
 impl<DataType: ChunkDataType + Copy> ChunkStorage<DataType> for PaletteStorage<DataType> {
     fn copy_dense(&self, output: &mut [DataType; CHUNK_DIM3Z]) {
         for (input, output) in self.iter().zip_eq(output.iter_mut()) {
@@ -420,6 +599,375 @@ impl<DataType: ChunkDataType + Copy> ChunkStorage<DataType> for PaletteStorage<D
     }
 }
 
+impl<DataType: ChunkDataType> ArrayStorage<DataType> {
+    /// Iterates over all the data in XZY order (with strides of X=1, Z=32, Y=32²).
+    pub fn iter(&self) -> impl Iterator<Item = &DataType> {
+        match self {
+            ArrayStorage::Singleton(value) => Either::Left(std::iter::repeat(value).take(CHUNK_DIM3Z)),
+            ArrayStorage::Array(array) => Either::Right(array.iter()),
+        }
+    }
+
+    /// Iterates over all the data paired with the block coordinates inside the chunk, in XZY order.
+    pub fn iter_with_coords(&self) -> impl Iterator<Item = (InChunkPos, &DataType)> {
+        self.iter().enumerate_xzy()
+    }
+
+    /// Checks if the storage is in the compact single-element representation.
+    pub fn is_singleton(&self) -> bool {
+        matches!(self, ArrayStorage::Singleton(_))
+    }
+
+    /// Approximate number of bytes used by this storage, including heap allocations.
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            ArrayStorage::Singleton(_) => std::mem::size_of::<Self>(),
+            ArrayStorage::Array(_) => std::mem::size_of::<Self>() + std::mem::size_of::<[DataType; CHUNK_DIM3Z]>(),
+        }
+    }
+
+    /// Collapses the array representation back into a [`ArrayStorage::Singleton`] if all the elements are identical, freeing the array.
+    ///
+    /// Returns true if the storage is a singleton after the call.
+    pub fn compact(&mut self) -> bool {
+        match self {
+            ArrayStorage::Singleton(_) => true,
+            ArrayStorage::Array(array) => {
+                let first = &array[0];
+                if array.iter().all(|v| v == first) {
+                    *self = ArrayStorage::Singleton(first.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Converts the storage into the array representation if needed, returning the array for modification.
+    fn expand(&mut self) -> &mut [DataType; CHUNK_DIM3Z] {
+        if let ArrayStorage::Singleton(value) = self {
+            let array: Box<[DataType; CHUNK_DIM3Z]> = vec![value.clone(); CHUNK_DIM3Z]
+                .into_boxed_slice()
+                .try_into()
+                .unwrap_or_else(|_| unreachable!());
+            *self = ArrayStorage::Array(array);
+        }
+        match self {
+            ArrayStorage::Singleton(_) => unreachable!(),
+            ArrayStorage::Array(array) => array,
+        }
+    }
+}
+
+impl<DataType: ChunkDataType> PartialEq for ArrayStorage<DataType> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ArrayStorage::Singleton(a), ArrayStorage::Singleton(b)) => a == b,
+            _ => self.iter().eq(other.iter()),
+        }
+    }
+}
+
+impl<DataType: ChunkDataType + Eq> Eq for ArrayStorage<DataType> {}
+
+impl<DataType: ChunkDataType> Default for ArrayStorage<DataType> {
+    fn default() -> Self {
+        ArrayStorage::Singleton(DataType::default())
+    }
+}
+
+impl<DataType: ChunkDataType> ChunkStorage<DataType> for ArrayStorage<DataType> {
+    fn copy_dense(&self, output: &mut [DataType; CHUNK_DIM3Z]) {
+        match self {
+            ArrayStorage::Singleton(value) => output.fill(value.clone()),
+            ArrayStorage::Array(array) => output.clone_from_slice(&array[..]),
+        }
+    }
+
+    #[inline]
+    fn get(&self, position: InChunkPos) -> &DataType {
+        match self {
+            ArrayStorage::Singleton(value) => value,
+            ArrayStorage::Array(array) => &array[position.as_index()],
+        }
+    }
+
+    #[inline]
+    fn get_copy(&self, position: InChunkPos) -> DataType
+    where
+        DataType: Copy,
+    {
+        *self.get(position)
+    }
+
+    #[inline]
+    fn put(&mut self, position: InChunkPos, new_value: DataType) -> DataType {
+        if let ArrayStorage::Singleton(value) = self {
+            if *value == new_value {
+                return new_value;
+            }
+        }
+        std::mem::replace(&mut self.expand()[position.as_index()], new_value)
+    }
+
+    fn fill(&mut self, range: InChunkRange, new_value: DataType) {
+        if range.is_empty() {
+            return;
+        }
+        if range == InChunkRange::WHOLE_CHUNK {
+            *self = ArrayStorage::Singleton(new_value);
+            return;
+        }
+        if let ArrayStorage::Singleton(value) = self {
+            if *value == new_value {
+                return;
+            }
+        }
+        let min = range.min();
+        let max = range.max();
+        let array = self.expand();
+        for (y, z) in iproduct!(min.y..=max.y, min.z..=max.z) {
+            let start_idx = (y * CHUNK_DIM2 + z * CHUNK_DIM + min.x) as usize;
+            let end_idx = (y * CHUNK_DIM2 + z * CHUNK_DIM + max.x) as usize;
+            array[start_idx..=end_idx].fill(new_value.clone());
+        }
+    }
+}
+
+impl<DataType: ChunkDataType> SparseStorage<DataType> {
+    /// Constructs an empty storage, where every element has the default value.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an empty storage with space preallocated for `capacity` non-default elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: HashMap::with_capacity(capacity),
+            default_value: DataType::default(),
+        }
+    }
+
+    /// Number of non-default elements stored.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Checks if every element in the chunk has the default value.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes all the stored elements, resetting the whole chunk to the default value.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Iterates over only the stored (non-default) elements paired with the block coordinates inside the chunk, in XZY order.
+    pub fn iter_with_coords(&self) -> impl Iterator<Item = (InChunkPos, &DataType)> {
+        self.data
+            .iter()
+            .sorted_unstable_by_key(|(&idx, _)| idx)
+            .map(|(&idx, val)| (InChunkPos::try_from_index(idx as usize).unwrap(), val))
+    }
+}
+
+impl<DataType: ChunkDataType> Default for SparseStorage<DataType> {
+    fn default() -> Self {
+        Self {
+            data: HashMap::new(),
+            default_value: DataType::default(),
+        }
+    }
+}
+
+impl<DataType: ChunkDataType> ChunkStorage<DataType> for SparseStorage<DataType> {
+    fn copy_dense(&self, output: &mut [DataType; CHUNK_DIM3Z]) {
+        output.fill(self.default_value.clone());
+        for (&idx, value) in self.data.iter() {
+            output[idx as usize] = value.clone();
+        }
+    }
+
+    #[inline]
+    fn get(&self, position: InChunkPos) -> &DataType {
+        self.data
+            .get(&(position.as_index() as u16))
+            .unwrap_or(&self.default_value)
+    }
+
+    #[inline]
+    fn get_copy(&self, position: InChunkPos) -> DataType
+    where
+        DataType: Copy,
+    {
+        *self.get(position)
+    }
+
+    fn put(&mut self, position: InChunkPos, new_value: DataType) -> DataType {
+        let idx = position.as_index() as u16;
+        let old_value = if new_value == self.default_value {
+            self.data.remove(&idx)
+        } else {
+            self.data.insert(idx, new_value)
+        };
+        old_value.unwrap_or_else(|| self.default_value.clone())
+    }
+
+    fn fill(&mut self, range: InChunkRange, new_value: DataType) {
+        if range.is_empty() {
+            return;
+        }
+        if new_value == self.default_value {
+            if range == InChunkRange::WHOLE_CHUNK {
+                self.data.clear();
+            } else {
+                for pos in range.iter_xzy() {
+                    self.data.remove(&(pos.as_index() as u16));
+                }
+            }
+        } else {
+            for pos in range.iter_xzy() {
+                self.data.insert(pos.as_index() as u16, new_value.clone());
+            }
+        }
+    }
+}
+
+// Serialization
+
+/// Serializes the palette indices of every element, remapped to skip unused palette entries.
+struct RemappedPaletteIndices<'s, DataType: ChunkDataType + Copy> {
+    storage: &'s PaletteStorage<DataType>,
+    remap: Vec<u16>,
+}
+
+impl<DataType: ChunkDataType + Copy> Serialize for RemappedPaletteIndices<'_, DataType> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.storage.iter_indices().map(|index| self.remap[index as usize]))
+    }
+}
+
+/// Serialized as the palette of used values and the palette index of every element in XZY order.
+/// The indices are left empty if the palette has a single value.
+impl<DataType: ChunkDataType + Copy + Serialize> Serialize for PaletteStorage<DataType> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut used = vec![false; self.palette.len()];
+        for index in self.iter_indices() {
+            used[index as usize] = true;
+        }
+        let mut palette = Vec::with_capacity(self.palette.len());
+        let mut remap = vec![0; self.palette.len()];
+        for ((slot, value), used) in remap.iter_mut().zip(self.palette.iter()).zip(used) {
+            if used {
+                *slot = palette.len() as u16;
+                palette.push(value);
+            }
+        }
+
+        let mut state = serializer.serialize_struct("PaletteStorage", 2)?;
+        state.serialize_field("palette", &palette)?;
+        if palette.len() == 1 {
+            state.serialize_field("indices", &[] as &[u16])?;
+        } else {
+            state.serialize_field("indices", &RemappedPaletteIndices { storage: self, remap })?;
+        }
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "PaletteStorage", deny_unknown_fields)]
+struct PaletteStorageRepr<DataType> {
+    palette: Vec<DataType>,
+    indices: Vec<u16>,
+}
+
+impl<'de, DataType: ChunkDataType + Copy + Eq + Deserialize<'de>> Deserialize<'de> for PaletteStorage<DataType> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = PaletteStorageRepr::<DataType>::deserialize(deserializer)?;
+        let indices: Box<[u16; CHUNK_DIM3Z]> = match repr.indices.len() {
+            0 => bytemuck::zeroed_box(),
+            CHUNK_DIM3Z => repr.indices.into_boxed_slice().try_into().unwrap(),
+            len => return Err(D::Error::invalid_length(len, &"0 or 32768 palette indices")),
+        };
+        Self::try_from_palette_and_indices(&repr.palette, &indices).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes every element of an [`ArrayStorage::Array`] in XZY order.
+struct ArrayElements<'s, DataType>(&'s [DataType; CHUNK_DIM3Z]);
+
+impl<DataType: Serialize> Serialize for ArrayElements<'_, DataType> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+/// Serialized as either the single value, or a sequence of every element in XZY order.
+impl<DataType: Serialize> Serialize for ArrayStorage<DataType> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ArrayStorage::Singleton(value) => {
+                serializer.serialize_newtype_variant("ArrayStorage", 0, "Singleton", value)
+            }
+            ArrayStorage::Array(array) => {
+                serializer.serialize_newtype_variant("ArrayStorage", 1, "Array", &ArrayElements(array))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "ArrayStorage")]
+enum ArrayStorageRepr<DataType> {
+    Singleton(DataType),
+    Array(Vec<DataType>),
+}
+
+impl<'de, DataType: Deserialize<'de>> Deserialize<'de> for ArrayStorage<DataType> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match ArrayStorageRepr::deserialize(deserializer)? {
+            ArrayStorageRepr::Singleton(value) => Ok(ArrayStorage::Singleton(value)),
+            ArrayStorageRepr::Array(elements) => match elements.into_boxed_slice().try_into() {
+                Ok(array) => Ok(ArrayStorage::Array(array)),
+                Err(elements) => Err(D::Error::invalid_length(elements.len(), &"32768 array elements")),
+            },
+        }
+    }
+}
+
+/// Serialized as a sequence of `(index, value)` pairs of the non-default elements, sorted by their XZY index.
+impl<DataType: ChunkDataType + Serialize> Serialize for SparseStorage<DataType> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.data.iter().sorted_unstable_by_key(|(&idx, _)| idx))
+    }
+}
+
+impl<'de, DataType: ChunkDataType + Deserialize<'de>> Deserialize<'de> for SparseStorage<DataType> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<(u16, DataType)>::deserialize(deserializer)?;
+        let mut storage = Self::with_capacity(entries.len());
+        for (idx, value) in entries {
+            if idx as usize >= CHUNK_DIM3Z {
+                return Err(D::Error::custom(format_args!(
+                    "sparse storage index {idx} is out of range"
+                )));
+            }
+            if value == storage.default_value {
+                return Err(D::Error::custom(format_args!(
+                    "sparse storage entry at index {idx} has the default value"
+                )));
+            }
+            if storage.data.insert(idx, value).is_some() {
+                return Err(D::Error::custom(format_args!("duplicate sparse storage index {idx}")));
+            }
+        }
+        Ok(storage)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -469,4 +1017,286 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn palette_map() {
+        let mut chunk: PaletteStorage<u64> = PaletteStorage::default();
+        for idx in 0..CHUNK_DIM3Z {
+            chunk.put(InChunkPos::try_from_index(idx).unwrap(), (idx % 10) as u64);
+        }
+        assert_eq!(chunk.try_map_palette(|&v| if v == 7 { Err(v) } else { Ok(v) }), Err(7));
+        for (pos, &val) in chunk.iter_with_coords() {
+            assert_eq!(val, (pos.as_index() % 10) as u64);
+        }
+
+        chunk.try_map_palette(|&v| Ok::<_, ()>(v + 100)).unwrap();
+        assert_eq!(chunk.palette().len(), 10);
+        for (pos, &val) in chunk.iter_with_coords() {
+            assert_eq!(val, (pos.as_index() % 10) as u64 + 100);
+        }
+
+        // Merge duplicates
+        chunk.try_map_palette(|&v| Ok::<_, ()>(v % 2)).unwrap();
+        assert_eq!(chunk.palette().len(), 2);
+        for (pos, &val) in chunk.iter_with_coords() {
+            assert_eq!(val, (pos.as_index() % 10) as u64 % 2);
+        }
+
+        // Values no longer present in the chunk are not passed to the function
+        for idx in (0..CHUNK_DIM3Z).filter(|idx| idx % 2 == 1) {
+            chunk.put(InChunkPos::try_from_index(idx).unwrap(), 0);
+        }
+        assert!(chunk.palette().contains(&1));
+        chunk.try_map_palette(|&v| if v == 1 { Err(v) } else { Ok(v) }).unwrap();
+        assert_eq!(chunk.palette(), [0]);
+    }
+
+    #[test]
+    fn palette_logical_eq() {
+        let pos = InChunkPos::try_new(1, 2, 3).unwrap();
+        let mut a: PaletteStorage<u64> = PaletteStorage::default();
+        a.fill(InChunkRange::WHOLE_CHUNK, 5);
+        a.put(pos, 7);
+        let mut b: PaletteStorage<u64> = PaletteStorage::default();
+        b.fill(InChunkRange::WHOLE_CHUNK, 7);
+        b.fill(InChunkRange::WHOLE_CHUNK, 5);
+        b.put(pos, 7);
+        b.put(InChunkPos::ZERO, 9);
+        b.put(InChunkPos::ZERO, 5);
+        assert_ne!(a.palette(), b.palette());
+        assert!(a == b);
+        b.put(InChunkPos::ZERO, 9);
+        assert!(a != b);
+        assert!(PaletteStorage::<u64>::default() == PaletteStorage::default());
+        assert!(PaletteStorage::<u64>::default() != a);
+    }
+
+    #[test]
+    fn palette_from_indices() {
+        let mut indices = [0; CHUNK_DIM3Z];
+        let filled = PaletteStorage::try_from_palette_and_indices(&[1u64, 2], &indices).unwrap();
+        assert!(filled == PaletteStorage::new_filled(1));
+        assert_eq!(filled.palette(), [1]);
+        indices[5] = 1;
+        let mixed = PaletteStorage::try_from_palette_and_indices(&[1u64, 2], &indices).unwrap();
+        assert_eq!(mixed.iter().filter(|&&v| v == 2).count(), 1);
+        assert_eq!(*mixed.get(InChunkPos::try_from_index(5).unwrap()), 2);
+        assert_eq!(
+            PaletteStorage::try_from_palette_and_indices(&[1u64, 1], &indices).err(),
+            Some(PaletteStorageError::DuplicateEntry(1))
+        );
+    }
+
+    #[test]
+    fn array_set() {
+        let mut chunk: ArrayStorage<u64> = ArrayStorage::default();
+        let zero_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+        let mut one_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+        one_arr.fill(1);
+        let mut out_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(0, chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()));
+        }
+        chunk.copy_dense(&mut out_arr);
+        assert_eq!(&zero_arr[..], &out_arr[..]);
+        assert!(chunk.is_singleton());
+
+        chunk.fill(InChunkRange::WHOLE_CHUNK, 1);
+        assert!(chunk.is_singleton());
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(1, chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()));
+        }
+        chunk.copy_dense(&mut out_arr);
+        assert_eq!(&one_arr[..], &out_arr[..]);
+
+        assert_eq!(chunk.put(InChunkPos::ZERO, 1), 1);
+        assert!(chunk.is_singleton());
+        for idx in 0..CHUNK_DIM3Z {
+            chunk.put(InChunkPos::try_from_index(idx).unwrap(), idx as u64);
+        }
+        assert!(!chunk.is_singleton());
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()), idx as u64);
+        }
+        assert!(!chunk.compact());
+
+        chunk.fill(InChunkRange::WHOLE_CHUNK, 1_000_000);
+        chunk.fill(
+            InChunkRange::from_corners(
+                InChunkPos::ZERO,
+                InChunkPos::try_new(CHUNK_DIM - 1, 8, CHUNK_DIM - 1).unwrap(),
+            ),
+            2_000_000,
+        );
+        assert!(!chunk.is_singleton());
+
+        for (pos, val) in chunk.iter_with_coords() {
+            if pos.y <= 8 {
+                assert_eq!(*val, 2_000_000);
+            } else {
+                assert_eq!(*val, 1_000_000);
+            }
+        }
+
+        chunk.fill(
+            InChunkRange::from_corners(InChunkPos::try_new(0, 9, 0).unwrap(), InChunkPos::MAX),
+            2_000_000,
+        );
+        assert!(!chunk.is_singleton());
+        assert!(chunk.compact());
+        assert!(chunk.is_singleton());
+        assert_eq!(chunk.get_copy(InChunkPos::MAX), 2_000_000);
+    }
+
+    #[test]
+    fn sparse_set() {
+        let mut chunk: SparseStorage<u64> = SparseStorage::default();
+        let zero_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+        let mut one_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+        one_arr.fill(1);
+        let mut out_arr: Box<[u64; CHUNK_DIM3Z]> = bytemuck::zeroed_box();
+
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(0, chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()));
+        }
+        chunk.copy_dense(&mut out_arr);
+        assert_eq!(&zero_arr[..], &out_arr[..]);
+        assert!(chunk.is_empty());
+
+        chunk.fill(InChunkRange::WHOLE_CHUNK, 1);
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(1, chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()));
+        }
+        chunk.copy_dense(&mut out_arr);
+        assert_eq!(&one_arr[..], &out_arr[..]);
+
+        for idx in 0..CHUNK_DIM3Z {
+            chunk.put(InChunkPos::try_from_index(idx).unwrap(), idx as u64);
+        }
+        for idx in 0..CHUNK_DIM3Z {
+            assert_eq!(chunk.get_copy(InChunkPos::try_from_index(idx).unwrap()), idx as u64);
+        }
+        // Index 0 was set to the default value, so it's not stored
+        assert_eq!(chunk.len(), CHUNK_DIM3Z - 1);
+
+        chunk.fill(InChunkRange::WHOLE_CHUNK, 0);
+        assert!(chunk.is_empty());
+        chunk.fill(
+            InChunkRange::from_corners(
+                InChunkPos::ZERO,
+                InChunkPos::try_new(CHUNK_DIM - 1, 8, CHUNK_DIM - 1).unwrap(),
+            ),
+            2_000_000,
+        );
+
+        let mut last_idx = None;
+        for (pos, val) in chunk.iter_with_coords() {
+            assert!(pos.y <= 8);
+            assert_eq!(*val, 2_000_000);
+            assert!(last_idx < Some(pos.as_index()));
+            last_idx = Some(pos.as_index());
+        }
+        assert_eq!(chunk.len(), CHUNK_DIM2Z * 9);
+        assert_eq!(chunk.put(InChunkPos::ZERO, 0), 2_000_000);
+        assert_eq!(chunk.put(InChunkPos::ZERO, 0), 0);
+        assert_eq!(chunk.len(), CHUNK_DIM2Z * 9 - 1);
+    }
+
+    #[test]
+    fn storage_serde() {
+        use serde_test::Token;
+
+        let mut palette: PaletteStorage<u64> = PaletteStorage::default();
+        palette.fill(InChunkRange::WHOLE_CHUNK, 5);
+        // The stale 0 entry is not written
+        serde_test::assert_ser_tokens(
+            &palette,
+            &[
+                Token::Struct {
+                    name: "PaletteStorage",
+                    len: 2,
+                },
+                Token::Str("palette"),
+                Token::Seq { len: Some(1) },
+                Token::U64(5),
+                Token::SeqEnd,
+                Token::Str("indices"),
+                Token::Seq { len: Some(0) },
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+        for idx in 0..CHUNK_DIM3Z {
+            palette.put(InChunkPos::try_from_index(idx).unwrap(), (idx % 300) as u64);
+        }
+        let ron = ron::to_string(&palette).unwrap();
+        assert!(ron::from_str::<PaletteStorage<u64>>(&ron).unwrap() == palette);
+        assert!(
+            ron::from_str::<PaletteStorage<u64>>("(palette: [1, 2], indices: [])").unwrap()
+                == PaletteStorage::new_filled(1)
+        );
+        let palette_error = |ron: &str| {
+            ron::from_str::<PaletteStorage<u64>>(ron)
+                .err()
+                .unwrap()
+                .code
+                .to_string()
+        };
+        assert!(palette_error("(palette: [], indices: [])").contains("empty"));
+        assert!(palette_error("(palette: [1, 1], indices: [])").contains("duplicate"));
+        assert!(palette_error("(palette: [1, 2], indices: [0, 1])").contains("32768"));
+        let mut out_of_range = "(palette: [1, 2], indices: [".to_owned() + &"0,".repeat(CHUNK_DIM3Z - 1);
+        out_of_range += "2])";
+        assert!(palette_error(&out_of_range).contains("out of range"));
+
+        let mut array: ArrayStorage<u16> = ArrayStorage::Singleton(7);
+        serde_test::assert_ser_tokens(
+            &array,
+            &[
+                Token::NewtypeVariant {
+                    name: "ArrayStorage",
+                    variant: "Singleton",
+                },
+                Token::U16(7),
+            ],
+        );
+        array.put(InChunkPos::MAX, 8);
+        let ron = ron::to_string(&array).unwrap();
+        let array_back: ArrayStorage<u16> = ron::from_str(&ron).unwrap();
+        assert!(!array_back.is_singleton());
+        assert!(array_back == array);
+        assert!(ron::from_str::<ArrayStorage<u16>>("Array([1, 2, 3])")
+            .err()
+            .unwrap()
+            .code
+            .to_string()
+            .contains("32768"));
+
+        let mut sparse: SparseStorage<u32> = SparseStorage::new();
+        sparse.put(InChunkPos::MAX, 3);
+        sparse.put(InChunkPos::ZERO, 1);
+        serde_test::assert_ser_tokens(
+            &sparse,
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Tuple { len: 2 },
+                Token::U16(0),
+                Token::U32(1),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U16(CHUNK_DIM3Z as u16 - 1),
+                Token::U32(3),
+                Token::TupleEnd,
+                Token::SeqEnd,
+            ],
+        );
+        let sparse_back: SparseStorage<u32> = ron::from_str(&ron::to_string(&sparse).unwrap()).unwrap();
+        assert!(sparse_back == sparse);
+        assert!(ron::from_str::<ArrayStorage<u16>>("Singleton(7)").unwrap() == ArrayStorage::Singleton(7));
+        let sparse_error = |ron: &str| ron::from_str::<SparseStorage<u32>>(ron).err().unwrap().code.to_string();
+        assert!(sparse_error("[(32768, 1)]").contains("out of range"));
+        assert!(sparse_error("[(1, 0)]").contains("default"));
+        assert!(sparse_error("[(1, 1), (1, 2)]").contains("duplicate"));
+    }
 }
@@ -1,8 +1,8 @@
 //! A collection of strongly typed newtype wrappers for the various coordinate formats within the game's world and related constants.
 
-use std::ops::{Add, Deref};
+use std::ops::{Add, AddAssign, BitAnd, BitOr, Deref, Neg, Not, Sub, SubAssign};
 
-use bevy_math::IVec3;
+use bevy_math::{IVec3, Vec3};
 use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -25,6 +25,15 @@ pub const CHUNK_DIM3Z: usize = (CHUNK_DIM * CHUNK_DIM * CHUNK_DIM) as usize;
 /// Chunk dimensions in blocks as a [IVec3] for convenience
 pub const CHUNK_DIM3V: IVec3 = IVec3::splat(CHUNK_DIM);
 
+/// Number of bits per axis used by the space-filling curve indices of [`AbsChunkPos`]
+pub const CHUNK_INDEX_AXIS_BITS: u32 = 21;
+/// Offset added to the chunk coordinates to make them non-negative in the space-filling curve indices
+const CHUNK_INDEX_BIAS: i32 = 1 << (CHUNK_INDEX_AXIS_BITS - 1);
+/// Mask of the valid bits in a single axis of a space-filling curve index
+const CHUNK_INDEX_AXIS_MASK: u64 = (1 << CHUNK_INDEX_AXIS_BITS) - 1;
+/// Mask of the valid bits in a space-filling curve index
+const CHUNK_INDEX_MASK: u64 = (1 << (3 * CHUNK_INDEX_AXIS_BITS)) - 1;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
 #[error("Given coordinates were outside of chunk boundaries: {0}")]
 /// Error when the given coordinates are outside of the chunk boundary.
@@ -35,14 +44,27 @@ pub struct InChunkVecError(IVec3);
 /// Error when the given block index is outside of the chunk boundary.
 pub struct InChunkIndexError(usize);
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+#[error("Given chunk coordinates were outside of the space-filling curve index range: {0}")]
+/// Error when the given chunk coordinates can't be represented in a 64-bit space-filling curve index.
+pub struct AbsChunkIndexVecError(IVec3);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+#[error("Given space-filling curve index was outside of the 63-bit range: {0}")]
+/// Error when the given space-filling curve index has bits set above the 63 bits used by the encoding.
+pub struct AbsChunkIndexError(u64);
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Pod, Zeroable, Serialize, Deserialize)]
 #[repr(transparent)]
 /// A block position inside of a chunk, limited to 0..=[CHUNK_DIM]
 pub struct InChunkPos(pub(crate) IVec3);
 
-#[derive(Copy, Clone, PartialEq, Hash, Debug, Default, Pod, Zeroable, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Hash, Debug, Pod, Zeroable, Serialize, Deserialize)]
 #[repr(C)]
 /// A range of block positions inside of a chunk, with coordinates limited to 0..[CHUNK_DIM] (min&max are *inclusive*)
+///
+/// As both corners are inclusive, a range with equal corners has one block, and the empty [`InChunkRange::ZERO`]
+/// has its minimum corner above the maximum corner.
 pub struct InChunkRange {
     pub(crate) min: InChunkPos,
     pub(crate) max: InChunkPos,
@@ -70,6 +92,62 @@ pub struct AbsBlockPos(pub(crate) IVec3);
 /// A block position relative to another block position
 pub struct RelBlockPos(pub(crate) IVec3);
 
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Pod, Zeroable, Serialize, Deserialize)]
+#[repr(C)]
+/// A range of absolute block positions in a voxel world (min&max are *inclusive*)
+pub struct AbsBlockRange {
+    pub(crate) min: AbsBlockPos,
+    pub(crate) max: AbsBlockPos,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Pod, Zeroable, Serialize, Deserialize)]
+#[repr(C)]
+/// A range of absolute chunk positions in a voxel world (min&max are *inclusive*)
+pub struct AbsChunkRange {
+    pub(crate) min: AbsChunkPos,
+    pub(crate) max: AbsChunkPos,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+#[repr(u8)]
+/// One of the 6 axis-aligned directions, also used to name the faces of a block or chunk.
+///
+/// The discriminant is the bit index of the direction in 6-bit face masks, such as [`crate::voxeltypes::BlockId::solid_sides_bits`].
+pub enum Direction {
+    /// +X
+    PosX = 0,
+    /// -X
+    NegX = 1,
+    /// +Y (up)
+    PosY = 2,
+    /// -Y (down)
+    NegY = 3,
+    /// +Z
+    PosZ = 4,
+    /// -Z
+    NegZ = 5,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Zeroable, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+#[repr(transparent)]
+/// A set of [`Direction`]s stored as a 6-bit mask, used e.g. for the solid sides of a block.
+pub struct Faces(u8);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+#[error("Given face mask has bits set outside of the 6 lowest bits: {0:#010b}")]
+/// Error when the given face bitmask has bits set that don't correspond to a [`Direction`].
+pub struct FacesBitsError(u8);
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+/// The result of stepping from an in-chunk position, which might end up in a neighbouring chunk.
+pub enum InChunkNeighbour {
+    /// The position is still inside the same chunk.
+    Inside(InChunkPos),
+    /// The position crossed the chunk border into the chunk at the given relative position.
+    Outside(RelChunkPos, InChunkPos),
+}
+
 // === Utils
 macro_rules! impl_simple_ivec3_newtype {
     ($T:ident) => {
@@ -99,6 +177,11 @@ macro_rules! impl_simple_ivec3_newtype {
             pub const fn new(x: i32, y: i32, z: i32) -> Self {
                 Self(IVec3::new(x, y, z))
             }
+
+            /// Same as `new(v, v, v)`
+            pub const fn splat(v: i32) -> Self {
+                Self(IVec3::splat(v))
+            }
         }
 
         impl From<IVec3> for $T {
@@ -202,6 +285,29 @@ impl InChunkPos {
     }
 }
 
+impl InChunkPos {
+    /// Offsets this position by the given vector, returning the chunk the result lands in (relative to this one) and the position inside of it.
+    #[inline]
+    pub const fn offset(self, offset: RelBlockPos) -> (RelChunkPos, InChunkPos) {
+        let (chunk, in_chunk) = AbsBlockPos(IVec3::new(
+            self.0.x + offset.0.x,
+            self.0.y + offset.0.y,
+            self.0.z + offset.0.z,
+        ))
+        .split_chunk();
+        (RelChunkPos(chunk.0), in_chunk)
+    }
+
+    /// Steps one block in the given direction, possibly crossing into a neighbouring chunk.
+    #[inline]
+    pub const fn neighbour(self, dir: Direction) -> InChunkNeighbour {
+        match self.offset(RelBlockPos(dir.to_ivec3())) {
+            (RelChunkPos(IVec3 { x: 0, y: 0, z: 0 }), pos) => InChunkNeighbour::Inside(pos),
+            (chunk, pos) => InChunkNeighbour::Outside(chunk, pos),
+        }
+    }
+}
+
 impl Add<InChunkPos> for InChunkPos {
     type Output = RelBlockPos;
     #[inline]
@@ -212,10 +318,13 @@ impl Add<InChunkPos> for InChunkPos {
 
 // === InChunkRange
 impl InChunkRange {
-    /// Empty range containing no blocks, originating at (0, 0, 0).
-    pub const ZERO: Self = Self::from_corners(InChunkPos::ZERO, InChunkPos::ZERO);
+    /// Empty range containing no blocks, represented by the minimum corner being above the maximum corner.
+    pub const ZERO: Self = Self {
+        min: InChunkPos::ONE,
+        max: InChunkPos::ZERO,
+    };
     /// A single block at (0, 0, 0).
-    pub const BLOCK_AT_ZERO: Self = Self::from_corners(InChunkPos::ZERO, InChunkPos::ONE);
+    pub const BLOCK_AT_ZERO: Self = Self::from_corners(InChunkPos::ZERO, InChunkPos::ZERO);
     /// The whole chunk `[(0, 0, 0), (31, 31, 31)]`.
     pub const WHOLE_CHUNK: Self = Self::from_corners(InChunkPos::ZERO, InChunkPos::MAX);
 
@@ -242,9 +351,9 @@ impl InChunkRange {
         Self { min, max }
     }
 
-    /// Checks if the range has any blocks, false if one or more of the dimensions are zero.
+    /// Checks if the range has no blocks, which is only possible for [`Self::ZERO`] as the corners are inclusive.
     pub const fn is_empty(self) -> bool {
-        (self.min.0.x == self.max.0.x) || (self.min.0.y == self.max.0.y) || (self.min.0.z == self.max.0.z)
+        (self.min.0.x > self.max.0.x) || (self.min.0.y > self.max.0.y) || (self.min.0.z > self.max.0.z)
     }
 
     /// Returns the corner with the smallest coordinates.
@@ -264,15 +373,985 @@ impl InChunkRange {
             self.min.z..=self.max.z,
             self.min.x..=self.max.x
         )
-        .map(|(y, z, x)| InChunkPos(IVec3::new(y, z, x)))
+        .map(|(y, z, x)| InChunkPos(IVec3::new(x, y, z)))
+    }
+}
+
+impl Default for InChunkRange {
+    /// The empty range, [`InChunkRange::ZERO`].
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+/// Implements the typed arithmetic between an absolute and relative position type pair:
+/// `Abs + Rel = Abs`, `Abs - Rel = Abs`, `Abs - Abs = Rel`, `Rel + Rel = Rel`, `Rel - Rel = Rel`, `-Rel = Rel`
+macro_rules! impl_abs_rel_ops {
+    ($Abs:ident, $Rel:ident) => {
+        impl Add<$Rel> for $Abs {
+            type Output = $Abs;
+            #[inline]
+            fn add(self, rhs: $Rel) -> Self::Output {
+                $Abs(self.0 + rhs.0)
+            }
+        }
+        impl AddAssign<$Rel> for $Abs {
+            #[inline]
+            fn add_assign(&mut self, rhs: $Rel) {
+                self.0 += rhs.0;
+            }
+        }
+        impl Sub<$Rel> for $Abs {
+            type Output = $Abs;
+            #[inline]
+            fn sub(self, rhs: $Rel) -> Self::Output {
+                $Abs(self.0 - rhs.0)
+            }
+        }
+        impl SubAssign<$Rel> for $Abs {
+            #[inline]
+            fn sub_assign(&mut self, rhs: $Rel) {
+                self.0 -= rhs.0;
+            }
+        }
+        impl Sub<$Abs> for $Abs {
+            type Output = $Rel;
+            #[inline]
+            fn sub(self, rhs: $Abs) -> Self::Output {
+                $Rel(self.0 - rhs.0)
+            }
+        }
+        impl Add<$Rel> for $Rel {
+            type Output = $Rel;
+            #[inline]
+            fn add(self, rhs: $Rel) -> Self::Output {
+                $Rel(self.0 + rhs.0)
+            }
+        }
+        impl AddAssign<$Rel> for $Rel {
+            #[inline]
+            fn add_assign(&mut self, rhs: $Rel) {
+                self.0 += rhs.0;
+            }
+        }
+        impl Sub<$Rel> for $Rel {
+            type Output = $Rel;
+            #[inline]
+            fn sub(self, rhs: $Rel) -> Self::Output {
+                $Rel(self.0 - rhs.0)
+            }
+        }
+        impl SubAssign<$Rel> for $Rel {
+            #[inline]
+            fn sub_assign(&mut self, rhs: $Rel) {
+                self.0 -= rhs.0;
+            }
+        }
+        impl Neg for $Rel {
+            type Output = $Rel;
+            #[inline]
+            fn neg(self) -> Self::Output {
+                $Rel(-self.0)
+            }
+        }
+    };
+}
+
+// === Direction
+impl Direction {
+    /// All the directions, in discriminant order.
+    pub const ALL: [Direction; 6] = [
+        Direction::PosX,
+        Direction::NegX,
+        Direction::PosY,
+        Direction::NegY,
+        Direction::PosZ,
+        Direction::NegZ,
+    ];
+    /// A 6-bit mask with all the directions set.
+    pub const ALL_BITMASK: u8 = 0b111111;
+
+    /// Converts the bit index back to a direction, or returns `None` if it's out of the `0..6` range.
+    pub const fn try_from_index(index: u8) -> Option<Self> {
+        if index < 6 {
+            Some(Self::ALL[index as usize])
+        } else {
+            None
+        }
+    }
+
+    /// The bit index of this direction in 6-bit masks.
+    #[inline]
+    pub const fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// The single-bit mask of this direction.
+    #[inline]
+    pub const fn to_bitmask(self) -> u8 {
+        1 << (self as u8)
+    }
+
+    /// Iterates over all the directions present in a 6-bit mask, higher bits are ignored.
+    pub fn iter_bitmask(mask: u8) -> impl Iterator<Item = Direction> {
+        Self::ALL.into_iter().filter(move |dir| mask & dir.to_bitmask() != 0)
+    }
+
+    /// Creates a 6-bit mask with the bits of all the given directions set.
+    pub fn collect_bitmask(dirs: impl IntoIterator<Item = Direction>) -> u8 {
+        dirs.into_iter().fold(0, |mask, dir| mask | dir.to_bitmask())
+    }
+
+    /// The direction pointing the opposite way.
+    #[inline]
+    pub const fn opposite(self) -> Self {
+        Self::ALL[(self as u8 ^ 1) as usize]
+    }
+
+    /// Unit vector pointing in this direction.
+    #[inline]
+    pub const fn to_ivec3(self) -> IVec3 {
+        match self {
+            Direction::PosX => IVec3::X,
+            Direction::NegX => IVec3::NEG_X,
+            Direction::PosY => IVec3::Y,
+            Direction::NegY => IVec3::NEG_Y,
+            Direction::PosZ => IVec3::Z,
+            Direction::NegZ => IVec3::NEG_Z,
+        }
+    }
+
+    /// Unit offset to the neighbouring block in this direction.
+    #[inline]
+    pub const fn to_rel_block(self) -> RelBlockPos {
+        RelBlockPos(self.to_ivec3())
+    }
+
+    /// Unit offset to the neighbouring chunk in this direction.
+    #[inline]
+    pub const fn to_rel_chunk(self) -> RelChunkPos {
+        RelChunkPos(self.to_ivec3())
+    }
+}
+
+// === Faces
+impl Faces {
+    /// No faces.
+    pub const NONE: Self = Self(0);
+    /// All 6 faces.
+    pub const ALL: Self = Self(Direction::ALL_BITMASK);
+
+    /// Constructs the set from a 6-bit mask, or returns an error if any higher bits are set.
+    pub const fn try_from_bits(bits: u8) -> Result<Self, FacesBitsError> {
+        if bits & !Direction::ALL_BITMASK != 0 {
+            Err(FacesBitsError(bits))
+        } else {
+            Ok(Self(bits))
+        }
+    }
+
+    /// Constructs the set from the lowest 6 bits of the mask, ignoring the rest.
+    pub const fn from_bits_truncate(bits: u8) -> Self {
+        Self(bits & Direction::ALL_BITMASK)
+    }
+
+    /// The 6-bit mask of this set, with bit indices given by [`Direction::index`].
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Checks if the given face is in the set.
+    pub const fn contains(self, dir: Direction) -> bool {
+        self.0 & dir.to_bitmask() != 0
+    }
+
+    /// Returns a copy of the set with the given face added.
+    pub const fn with(self, dir: Direction) -> Self {
+        Self(self.0 | dir.to_bitmask())
+    }
+
+    /// Returns a copy of the set with the given face removed.
+    pub const fn without(self, dir: Direction) -> Self {
+        Self(self.0 & !dir.to_bitmask())
+    }
+
+    /// Checks if the set has no faces.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Number of faces in the set.
+    pub const fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Iterates over the faces in the set, in [`Direction`] discriminant order.
+    pub fn iter(self) -> impl Iterator<Item = Direction> {
+        Direction::iter_bitmask(self.0)
+    }
+}
+
+impl TryFrom<u8> for Faces {
+    type Error = FacesBitsError;
+
+    fn try_from(bits: u8) -> Result<Self, Self::Error> {
+        Self::try_from_bits(bits)
+    }
+}
+
+impl From<Faces> for u8 {
+    fn from(faces: Faces) -> Self {
+        faces.0
+    }
+}
+
+impl From<Direction> for Faces {
+    fn from(value: Direction) -> Self {
+        Self(value.to_bitmask())
+    }
+}
+
+impl FromIterator<Direction> for Faces {
+    fn from_iter<T: IntoIterator<Item = Direction>>(iter: T) -> Self {
+        Self(Direction::collect_bitmask(iter))
+    }
+}
+
+impl BitOr for Faces {
+    type Output = Faces;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Faces {
+    type Output = Faces;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl Not for Faces {
+    type Output = Faces;
+    fn not(self) -> Self::Output {
+        Self(!self.0 & Direction::ALL_BITMASK)
+    }
+}
+
+/// Iterates over all the offsets in the 3x3x3 cube around the origin (excluding it) with at most `max_axes` non-zero components.
+fn neighbourhood_offsets(max_axes: i32) -> impl Iterator<Item = IVec3> {
+    itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+        .map(|(y, z, x)| IVec3::new(x, y, z))
+        .filter(move |v| {
+            let axes = v.abs().dot(IVec3::ONE);
+            axes > 0 && axes <= max_axes
+        })
+}
+
+/// Implements the common operations on an axis-aligned range of absolute positions (with inclusive corners).
+macro_rules! impl_abs_range {
+    ($Range:ident, $Pos:ident) => {
+        impl $Range {
+            /// A range containing just the position (0, 0, 0).
+            /// Unlike [`InChunkRange::ZERO`], which is empty, this range contains one position.
+            pub const ORIGIN: Self = Self::single($Pos::ZERO);
+
+            /// Constructs a new range from two (inclusive) corner positions.
+            pub fn from_corners(a: $Pos, b: $Pos) -> Self {
+                Self {
+                    min: $Pos(a.0.min(b.0)),
+                    max: $Pos(a.0.max(b.0)),
+                }
+            }
+
+            /// Constructs a range containing exactly one position.
+            pub const fn single(pos: $Pos) -> Self {
+                Self { min: pos, max: pos }
+            }
+
+            /// Returns the corner with the smallest coordinates.
+            pub const fn min(self) -> $Pos {
+                self.min
+            }
+
+            /// Returns the corner with the largest coordinates.
+            pub const fn max(self) -> $Pos {
+                self.max
+            }
+
+            /// Number of positions along each axis.
+            pub fn size(self) -> IVec3 {
+                self.max.0 - self.min.0 + IVec3::ONE
+            }
+
+            /// Number of positions contained in this range.
+            pub fn volume(self) -> u64 {
+                let size = self.size();
+                size.x as u64 * size.y as u64 * size.z as u64
+            }
+
+            /// Checks if the position is inside this range.
+            pub fn contains(self, pos: $Pos) -> bool {
+                pos.0.cmpge(self.min.0).all() && pos.0.cmple(self.max.0).all()
+            }
+
+            /// Checks if the other range is entirely inside this range.
+            pub fn contains_range(self, other: Self) -> bool {
+                self.contains(other.min) && self.contains(other.max)
+            }
+
+            /// Checks if the two ranges have at least one position in common.
+            pub fn intersects(self, other: Self) -> bool {
+                self.intersection(other).is_some()
+            }
+
+            /// The range of positions inside both ranges, or `None` if they don't overlap.
+            pub fn intersection(self, other: Self) -> Option<Self> {
+                let min = self.min.0.max(other.min.0);
+                let max = self.max.0.min(other.max.0);
+                if min.cmple(max).all() {
+                    Some(Self {
+                        min: $Pos(min),
+                        max: $Pos(max),
+                    })
+                } else {
+                    None
+                }
+            }
+
+            /// The smallest range containing both ranges (their bounding box).
+            pub fn union(self, other: Self) -> Self {
+                Self {
+                    min: $Pos(self.min.0.min(other.min.0)),
+                    max: $Pos(self.max.0.max(other.max.0)),
+                }
+            }
+
+            /// Returns an iterator over all the coordinates inside this range, in XZY order.
+            pub fn iter_xzy(self) -> impl Iterator<Item = $Pos> {
+                itertools::iproduct!(
+                    self.min.y..=self.max.y,
+                    self.min.z..=self.max.z,
+                    self.min.x..=self.max.x
+                )
+                .map(|(y, z, x)| $Pos(IVec3::new(x, y, z)))
+            }
+        }
+    };
+}
+
+// === AbsBlockRange
+impl_abs_range!(AbsBlockRange, AbsBlockPos);
+
+impl AbsBlockRange {
+    /// The range of chunks that contain at least one block of this range.
+    pub fn chunk_range(self) -> AbsChunkRange {
+        AbsChunkRange {
+            min: self.min.chunk(),
+            max: self.max.chunk(),
+        }
+    }
+
+    /// Splits the range along chunk borders, iterating over the intersection of this range with every chunk it touches in XZY chunk order.
+    /// Useful for performing a world-level operation via the per-chunk [`crate::chunk_storage::ChunkStorage`] methods.
+    pub fn split_chunks(self) -> impl Iterator<Item = (AbsChunkPos, InChunkRange)> {
+        self.chunk_range().iter_xzy().map(move |chunk| {
+            let piece = self
+                .intersection(chunk.block_range())
+                .expect("Chunk range computed from block range doesn't intersect the block range");
+            let range = InChunkRange {
+                min: piece.min.in_chunk(),
+                max: piece.max.in_chunk(),
+            };
+            (chunk, range)
+        })
+    }
+}
+
+// === AbsChunkRange
+impl_abs_range!(AbsChunkRange, AbsChunkPos);
+
+impl AbsChunkRange {
+    /// The range of all the blocks contained in the chunks of this range.
+    pub fn block_range(self) -> AbsBlockRange {
+        AbsBlockRange {
+            min: self.min.min_block(),
+            max: self.max.max_block(),
+        }
+    }
+}
+
+/// Spreads the lowest 21 bits of the value out to every 3rd bit.
+const fn morton_spread(v: u64) -> u64 {
+    let mut v = v & CHUNK_INDEX_AXIS_MASK;
+    v = (v | (v << 32)) & 0x001F_0000_0000_FFFF;
+    v = (v | (v << 16)) & 0x001F_0000_FF00_00FF;
+    v = (v | (v << 8)) & 0x100F_00F0_0F00_F00F;
+    v = (v | (v << 4)) & 0x10C3_0C30_C30C_30C3;
+    v = (v | (v << 2)) & 0x1249_2492_4924_9249;
+    v
+}
+
+/// Inverse of [`morton_spread`], gathers every 3rd bit into the lowest 21 bits.
+const fn morton_compact(v: u64) -> u64 {
+    let mut v = v & 0x1249_2492_4924_9249;
+    v = (v | (v >> 2)) & 0x10C3_0C30_C30C_30C3;
+    v = (v | (v >> 4)) & 0x100F_00F0_0F00_F00F;
+    v = (v | (v >> 8)) & 0x001F_0000_FF00_00FF;
+    v = (v | (v >> 16)) & 0x001F_0000_0000_FFFF;
+    v = (v | (v >> 32)) & CHUNK_INDEX_AXIS_MASK;
+    v
+}
+
+/// Interleaves three 21-bit values into `...y1 z1 x1 y0 z0 x0`.
+const fn morton_interleave(x: u64, y: u64, z: u64) -> u64 {
+    morton_spread(x) | (morton_spread(z) << 1) | (morton_spread(y) << 2)
+}
+
+/// Inverse of [`morton_interleave`], returns `[x, y, z]`.
+const fn morton_deinterleave(v: u64) -> [u64; 3] {
+    [morton_compact(v), morton_compact(v >> 2), morton_compact(v >> 1)]
+}
+
+/// Converts coordinates into the "transposed" Hilbert index form, whose bits interleaved (first axis most significant) make up the index.
+/// Based on John Skilling's "Programming the Hilbert curve" (AIP Conf. Proc. 707, 2004).
+const fn hilbert_axes_to_transpose(mut axes: [u64; 3]) -> [u64; 3] {
+    let m: u64 = 1 << (CHUNK_INDEX_AXIS_BITS - 1);
+    // Inverse undo
+    let mut q = m;
+    while q > 1 {
+        let p = q - 1;
+        let mut i = 0;
+        while i < 3 {
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+            i += 1;
+        }
+        q >>= 1;
+    }
+    // Gray encode
+    axes[1] ^= axes[0];
+    axes[2] ^= axes[1];
+    let mut t = 0;
+    q = m;
+    while q > 1 {
+        if axes[2] & q != 0 {
+            t ^= q - 1;
+        }
+        q >>= 1;
+    }
+    axes[0] ^= t;
+    axes[1] ^= t;
+    axes[2] ^= t;
+    axes
+}
+
+/// Inverse of [`hilbert_axes_to_transpose`].
+const fn hilbert_transpose_to_axes(mut axes: [u64; 3]) -> [u64; 3] {
+    let n: u64 = 2 << (CHUNK_INDEX_AXIS_BITS - 1);
+    // Gray decode
+    let t = axes[2] >> 1;
+    axes[2] ^= axes[1];
+    axes[1] ^= axes[0];
+    axes[0] ^= t;
+    // Undo excess work
+    let mut q = 2;
+    while q != n {
+        let p = q - 1;
+        let mut i = 3;
+        while i > 0 {
+            i -= 1;
+            if axes[i] & q != 0 {
+                axes[0] ^= p;
+            } else {
+                let t = (axes[0] ^ axes[i]) & p;
+                axes[0] ^= t;
+                axes[i] ^= t;
+            }
+        }
+        q <<= 1;
     }
+    axes
 }
 
 // === AbsChunkPos
 impl_simple_ivec3_newtype!(AbsChunkPos);
+impl_abs_rel_ops!(AbsChunkPos, RelChunkPos);
+
+impl AbsChunkPos {
+    /// Returns the absolute position of the block at the given position inside this chunk.
+    #[inline]
+    pub const fn block_at(self, in_chunk: InChunkPos) -> AbsBlockPos {
+        AbsBlockPos::join_chunk(self, in_chunk)
+    }
+
+    /// Returns the absolute position of the block with the smallest coordinates in this chunk.
+    #[inline]
+    pub const fn min_block(self) -> AbsBlockPos {
+        self.block_at(InChunkPos::ZERO)
+    }
+
+    /// Returns the absolute position of the block with the largest coordinates in this chunk.
+    #[inline]
+    pub const fn max_block(self) -> AbsBlockPos {
+        self.block_at(InChunkPos::MAX)
+    }
+
+    /// Smallest coordinate (on every axis) representable in the space-filling curve indices.
+    pub const INDEX_MIN_COORD: i32 = -CHUNK_INDEX_BIAS;
+    /// Largest coordinate (on every axis) representable in the space-filling curve indices.
+    pub const INDEX_MAX_COORD: i32 = CHUNK_INDEX_BIAS - 1;
+
+    /// Offsets the coordinates into the unsigned range used by the curve indices.
+    const fn try_biased_axes(self) -> Result<[u64; 3], AbsChunkIndexVecError> {
+        let IVec3 { x, y, z } = self.0;
+        if x < Self::INDEX_MIN_COORD
+            || x > Self::INDEX_MAX_COORD
+            || y < Self::INDEX_MIN_COORD
+            || y > Self::INDEX_MAX_COORD
+            || z < Self::INDEX_MIN_COORD
+            || z > Self::INDEX_MAX_COORD
+        {
+            return Err(AbsChunkIndexVecError(self.0));
+        }
+        Ok([
+            (x + CHUNK_INDEX_BIAS) as u64,
+            (y + CHUNK_INDEX_BIAS) as u64,
+            (z + CHUNK_INDEX_BIAS) as u64,
+        ])
+    }
+
+    /// Inverse of [`Self::try_biased_axes`]
+    const fn from_biased_axes(x: u64, y: u64, z: u64) -> Self {
+        Self(IVec3::new(
+            x as i32 - CHUNK_INDEX_BIAS,
+            y as i32 - CHUNK_INDEX_BIAS,
+            z as i32 - CHUNK_INDEX_BIAS,
+        ))
+    }
+
+    /// Converts the coordinates into a Morton (Z-order) index, with the bits of each axis interleaved in the YZX order (X being the least significant).
+    /// Nearby chunks usually have nearby indices, and aligned power-of-two cubes of chunks occupy contiguous index ranges.
+    ///
+    /// Every coordinate must be in the range [`Self::INDEX_MIN_COORD`]..=[`Self::INDEX_MAX_COORD`].
+    pub const fn try_as_morton_index(self) -> Result<u64, AbsChunkIndexVecError> {
+        match self.try_biased_axes() {
+            Ok([x, y, z]) => Ok(morton_interleave(x, y, z)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Converts a Morton index created by [`Self::try_as_morton_index`] back into the coordinates.
+    pub const fn try_from_morton_index(index: u64) -> Result<Self, AbsChunkIndexError> {
+        if index & !CHUNK_INDEX_MASK != 0 {
+            return Err(AbsChunkIndexError(index));
+        }
+        let [x, y, z] = morton_deinterleave(index);
+        Ok(Self::from_biased_axes(x, y, z))
+    }
+
+    /// Converts the coordinates into a 3D Hilbert curve index, which has better locality than the Morton order:
+    /// chunks with consecutive indices are always direct neighbours.
+    ///
+    /// Every coordinate must be in the range [`Self::INDEX_MIN_COORD`]..=[`Self::INDEX_MAX_COORD`].
+    pub const fn try_as_hilbert_index(self) -> Result<u64, AbsChunkIndexVecError> {
+        match self.try_biased_axes() {
+            Ok([x, y, z]) => {
+                let [t0, t1, t2] = hilbert_axes_to_transpose([y, z, x]);
+                Ok(morton_interleave(t2, t0, t1))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Converts a Hilbert index created by [`Self::try_as_hilbert_index`] back into the coordinates.
+    pub const fn try_from_hilbert_index(index: u64) -> Result<Self, AbsChunkIndexError> {
+        if index & !CHUNK_INDEX_MASK != 0 {
+            return Err(AbsChunkIndexError(index));
+        }
+        let [t2, t0, t1] = morton_deinterleave(index);
+        let [y, z, x] = hilbert_transpose_to_axes([t0, t1, t2]);
+        Ok(Self::from_biased_axes(x, y, z))
+    }
+
+    /// Returns the range of all the blocks in this chunk.
+    #[inline]
+    pub fn block_range(self) -> AbsBlockRange {
+        AbsBlockRange {
+            min: self.min_block(),
+            max: self.max_block(),
+        }
+    }
+
+    /// Returns the neighbouring chunk position in the given direction.
+    #[inline]
+    pub fn neighbour(self, dir: Direction) -> AbsChunkPos {
+        self + dir.to_rel_chunk()
+    }
+
+    /// Iterates over the 6 chunks sharing a face with this chunk.
+    pub fn neighbours6(self) -> impl Iterator<Item = AbsChunkPos> {
+        neighbourhood_offsets(1).map(move |off| AbsChunkPos(self.0 + off))
+    }
+
+    /// Iterates over the 18 chunks sharing a face or an edge with this chunk.
+    pub fn neighbours18(self) -> impl Iterator<Item = AbsChunkPos> {
+        neighbourhood_offsets(2).map(move |off| AbsChunkPos(self.0 + off))
+    }
+
+    /// Iterates over the 26 chunks sharing a face, an edge or a corner with this chunk.
+    pub fn neighbours26(self) -> impl Iterator<Item = AbsChunkPos> {
+        neighbourhood_offsets(3).map(move |off| AbsChunkPos(self.0 + off))
+    }
+}
+
 // === RelChunkPos
 impl_simple_ivec3_newtype!(RelChunkPos);
+
 // === AbsBlockPos
 impl_simple_ivec3_newtype!(AbsBlockPos);
+impl_abs_rel_ops!(AbsBlockPos, RelBlockPos);
+
+impl AbsBlockPos {
+    /// Splits the position into the position of the containing chunk and the position inside of that chunk.
+    /// Uses floor division, so e.g. block `-1` is in chunk `-1` at in-chunk position `31`.
+    #[inline]
+    pub const fn split_chunk(self) -> (AbsChunkPos, InChunkPos) {
+        let IVec3 { x, y, z } = self.0;
+        (
+            AbsChunkPos(IVec3::new(
+                x.div_euclid(CHUNK_DIM),
+                y.div_euclid(CHUNK_DIM),
+                z.div_euclid(CHUNK_DIM),
+            )),
+            InChunkPos(IVec3::new(
+                x.rem_euclid(CHUNK_DIM),
+                y.rem_euclid(CHUNK_DIM),
+                z.rem_euclid(CHUNK_DIM),
+            )),
+        )
+    }
+
+    /// Returns the position of the chunk containing this block.
+    #[inline]
+    pub const fn chunk(self) -> AbsChunkPos {
+        self.split_chunk().0
+    }
+
+    /// Returns the position of this block inside its chunk.
+    #[inline]
+    pub const fn in_chunk(self) -> InChunkPos {
+        self.split_chunk().1
+    }
+
+    /// The inverse of [`Self::split_chunk`], combines a chunk position and an in-chunk position into an absolute block position.
+    #[inline]
+    pub const fn join_chunk(chunk: AbsChunkPos, in_chunk: InChunkPos) -> Self {
+        Self(IVec3::new(
+            chunk.0.x * CHUNK_DIM + in_chunk.0.x,
+            chunk.0.y * CHUNK_DIM + in_chunk.0.y,
+            chunk.0.z * CHUNK_DIM + in_chunk.0.z,
+        ))
+    }
+
+    /// World-space coordinates (in meters) of the corner of this block with the smallest coordinates.
+    #[inline]
+    pub fn to_world_vec3(self) -> Vec3 {
+        self.0.as_vec3() * BLOCK_DIM
+    }
+
+    /// World-space coordinates (in meters) of the center of this block.
+    #[inline]
+    pub fn center_world_vec3(self) -> Vec3 {
+        (self.0.as_vec3() + Vec3::splat(0.5)) * BLOCK_DIM
+    }
+
+    /// Returns the position of the block containing the given world-space point (in meters).
+    #[inline]
+    pub fn from_world_vec3(world: Vec3) -> Self {
+        Self((world / BLOCK_DIM).floor().as_ivec3())
+    }
+}
+
+impl From<AbsBlockPos> for (AbsChunkPos, InChunkPos) {
+    #[inline]
+    fn from(value: AbsBlockPos) -> Self {
+        value.split_chunk()
+    }
+}
+
+impl From<(AbsChunkPos, InChunkPos)> for AbsBlockPos {
+    #[inline]
+    fn from((chunk, in_chunk): (AbsChunkPos, InChunkPos)) -> Self {
+        Self::join_chunk(chunk, in_chunk)
+    }
+}
+
 // === RelBlockPos
 impl_simple_ivec3_newtype!(RelBlockPos);
+
+#[cfg(test)]
+mod test {
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+
+    #[test]
+    fn split_negative_blocks() {
+        assert_eq!(
+            AbsBlockPos::new(-1, 0, 31).split_chunk(),
+            (AbsChunkPos::new(-1, 0, 0), InChunkPos::try_new(31, 0, 31).unwrap())
+        );
+        assert_eq!(
+            AbsBlockPos::new(-32, -33, 32).split_chunk(),
+            (AbsChunkPos::new(-1, -2, 1), InChunkPos::try_new(0, 31, 0).unwrap())
+        );
+        assert_eq!(AbsChunkPos::new(-1, 0, 2).min_block(), AbsBlockPos::new(-32, 0, 64));
+        assert_eq!(AbsChunkPos::new(-1, 0, 2).max_block(), AbsBlockPos::new(-1, 31, 95));
+    }
+
+    #[test]
+    fn world_vec3() {
+        assert_eq!(AbsBlockPos::new(1, -2, 0).to_world_vec3(), Vec3::new(0.5, -1.0, 0.0));
+        assert_eq!(
+            AbsBlockPos::from_world_vec3(Vec3::new(0.5, -0.01, 0.49)),
+            AbsBlockPos::new(1, -1, 0)
+        );
+        assert_eq!(AbsBlockPos::new(0, 0, 0).center_world_vec3(), Vec3::splat(0.25));
+    }
+
+    #[test]
+    fn typed_arithmetic() {
+        let a = AbsBlockPos::new(1, 2, 3);
+        let b = AbsBlockPos::new(-4, 5, 6);
+        let rel: RelBlockPos = b - a;
+        assert_eq!(rel, RelBlockPos::new(-5, 3, 3));
+        assert_eq!(a + rel, b);
+        assert_eq!(b - rel, a);
+        assert_eq!(-rel + rel, RelBlockPos::ZERO);
+        let mut c = AbsChunkPos::ZERO;
+        c += RelChunkPos::X;
+        c -= RelChunkPos::Y;
+        assert_eq!(c, AbsChunkPos::new(1, -1, 0));
+    }
+
+    #[test]
+    fn directions() {
+        for (idx, dir) in Direction::ALL.into_iter().enumerate() {
+            assert_eq!(Direction::try_from_index(idx as u8), Some(dir));
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_ne!(dir.opposite(), dir);
+            assert_eq!(dir.opposite().to_ivec3(), -dir.to_ivec3());
+            assert_eq!(dir.to_bitmask(), 1 << idx);
+        }
+        assert_eq!(Direction::try_from_index(6), None);
+        assert_eq!(Direction::collect_bitmask(Direction::ALL), Direction::ALL_BITMASK);
+        let mask = Direction::PosY.to_bitmask() | Direction::NegZ.to_bitmask();
+        assert_eq!(
+            Direction::iter_bitmask(mask).collect::<Vec<_>>(),
+            vec![Direction::PosY, Direction::NegZ]
+        );
+    }
+
+    #[test]
+    fn faces_set() {
+        let faces = Faces::from(Direction::PosX) | Faces::from(Direction::NegY);
+        assert!(faces.contains(Direction::PosX) && faces.contains(Direction::NegY));
+        assert!(!faces.contains(Direction::PosY));
+        assert_eq!(faces.len(), 2);
+        assert_eq!(faces.iter().collect::<Faces>(), faces);
+        assert_eq!(!faces & faces, Faces::NONE);
+        assert_eq!(!faces | faces, Faces::ALL);
+        assert_eq!(faces.without(Direction::PosX).with(Direction::PosZ).bits(), 0b011000);
+        assert_eq!(Faces::try_from_bits(0b111111), Ok(Faces::ALL));
+        assert!(Faces::try_from_bits(0b1000000).is_err());
+        assert_eq!(Faces::from_bits_truncate(0xFF), Faces::ALL);
+        assert_eq!(ron::from_str::<Faces>("63"), Ok(Faces::ALL));
+        assert!(ron::from_str::<Faces>("64").is_err());
+        assert_eq!(ron::to_string(&faces).unwrap(), faces.bits().to_string());
+    }
+
+    #[test]
+    fn in_chunk_neighbours() {
+        let mid = InChunkPos::try_new(5, 6, 7).unwrap();
+        assert_eq!(
+            mid.neighbour(Direction::PosX),
+            InChunkNeighbour::Inside(InChunkPos::try_new(6, 6, 7).unwrap())
+        );
+        assert_eq!(
+            InChunkPos::ZERO.neighbour(Direction::NegY),
+            InChunkNeighbour::Outside(RelChunkPos::new(0, -1, 0), InChunkPos::try_new(0, 31, 0).unwrap())
+        );
+        assert_eq!(
+            InChunkPos::MAX.neighbour(Direction::PosZ),
+            InChunkNeighbour::Outside(RelChunkPos::Z, InChunkPos::try_new(31, 31, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn chunk_neighbourhoods() {
+        let origin = AbsChunkPos::new(10, -3, 2);
+        for (iter, expected) in [
+            (origin.neighbours6().collect::<Vec<_>>(), 6),
+            (origin.neighbours18().collect(), 18),
+            (origin.neighbours26().collect(), 26),
+        ] {
+            assert_eq!(iter.len(), expected);
+            assert!(!iter.contains(&origin));
+            assert!(iter.iter().all(|n| (*n - origin).abs().max_element() == 1));
+            assert_eq!(iter.iter().collect::<std::collections::HashSet<_>>().len(), expected);
+        }
+        for dir in Direction::ALL {
+            assert!(origin.neighbours6().any(|n| n == origin.neighbour(dir)));
+        }
+    }
+
+    #[test]
+    fn in_chunk_range_iter_order() {
+        let range = InChunkRange::from_corners(InChunkPos::ZERO, InChunkPos::try_new(1, 1, 1).unwrap());
+        let expected = [
+            (0, 0, 0),
+            (1, 0, 0),
+            (0, 0, 1),
+            (1, 0, 1),
+            (0, 1, 0),
+            (1, 1, 0),
+            (0, 1, 1),
+            (1, 1, 1),
+        ]
+        .map(|(x, y, z)| InChunkPos::try_new(x, y, z).unwrap());
+        assert_eq!(range.iter_xzy().collect::<Vec<_>>(), expected);
+        let flat = InChunkRange::from_corners(InChunkPos::ZERO, InChunkPos::try_new(CHUNK_DIM - 1, 0, 1).unwrap());
+        assert!(flat.iter_xzy().all(|pos| pos.y == 0));
+    }
+
+    #[test]
+    fn in_chunk_range_emptiness() {
+        assert!(InChunkRange::ZERO.is_empty());
+        assert_eq!(InChunkRange::ZERO.iter_xzy().count(), 0);
+        assert!(!InChunkRange::BLOCK_AT_ZERO.is_empty());
+        assert_eq!(InChunkRange::BLOCK_AT_ZERO.iter_xzy().count(), 1);
+        assert_eq!(InChunkRange::WHOLE_CHUNK.iter_xzy().count(), CHUNK_DIM3Z);
+        assert_eq!(InChunkRange::default(), InChunkRange::ZERO);
+    }
+
+    #[test]
+    fn abs_block_range_ops() {
+        let a = AbsBlockRange::from_corners(AbsBlockPos::new(4, 5, 6), AbsBlockPos::new(-2, 0, 1));
+        assert_eq!(a.min(), AbsBlockPos::new(-2, 0, 1));
+        assert_eq!(a.max(), AbsBlockPos::new(4, 5, 6));
+        assert_eq!(a.size(), IVec3::new(7, 6, 6));
+        assert_eq!(a.volume(), 7 * 6 * 6);
+        assert_eq!(a.iter_xzy().count() as u64, a.volume());
+        assert!(a.iter_xzy().all(|p| a.contains(p)));
+        assert!(!a.contains(AbsBlockPos::new(5, 5, 6)));
+
+        let b = AbsBlockRange::from_corners(AbsBlockPos::new(3, 3, 3), AbsBlockPos::new(10, 10, 10));
+        let ab = a.intersection(b).unwrap();
+        assert_eq!(
+            ab,
+            AbsBlockRange::from_corners(AbsBlockPos::new(3, 3, 3), AbsBlockPos::new(4, 5, 6))
+        );
+        assert!(a.contains_range(ab) && b.contains_range(ab));
+        let u = a.union(b);
+        assert_eq!(
+            u,
+            AbsBlockRange::from_corners(AbsBlockPos::new(-2, 0, 1), AbsBlockPos::new(10, 10, 10))
+        );
+        assert!(u.contains_range(a) && u.contains_range(b));
+        let far = AbsBlockRange::single(AbsBlockPos::new(100, 0, 0));
+        assert_eq!(a.intersection(far), None);
+        assert!(!a.intersects(far));
+        assert_eq!(far.volume(), 1);
+        assert_eq!(
+            AbsBlockRange::ORIGIN.iter_xzy().collect::<Vec<_>>(),
+            [AbsBlockPos::ZERO]
+        );
+        assert_eq!(AbsChunkRange::ORIGIN.volume(), 1);
+    }
+
+    #[test]
+    fn abs_block_range_split() {
+        let range = AbsBlockRange::from_corners(AbsBlockPos::new(-33, 0, 5), AbsBlockPos::new(31, 32, 5));
+        let pieces: Vec<_> = range.split_chunks().collect();
+        assert_eq!(range.chunk_range().volume(), 6);
+        assert_eq!(pieces.len(), 6);
+        let mut total = 0;
+        for (chunk, piece) in pieces {
+            assert!(!piece.is_empty());
+            for pos in piece.iter_xzy() {
+                assert!(range.contains(chunk.block_at(pos)));
+                total += 1;
+            }
+        }
+        assert_eq!(total, range.volume());
+        assert_eq!(
+            AbsChunkRange::single(AbsChunkPos::new(-1, 0, 0)).block_range(),
+            AbsChunkPos::new(-1, 0, 0).block_range()
+        );
+    }
+
+    #[test]
+    fn chunk_curve_indices() {
+        let min = AbsChunkPos::splat(AbsChunkPos::INDEX_MIN_COORD);
+        let max = AbsChunkPos::splat(AbsChunkPos::INDEX_MAX_COORD);
+        assert_eq!(min.try_as_morton_index(), Ok(0));
+        assert_eq!(max.try_as_morton_index(), Ok(CHUNK_INDEX_MASK));
+        assert_eq!((min + RelChunkPos::X).try_as_morton_index(), Ok(0b001));
+        assert_eq!((min + RelChunkPos::Z).try_as_morton_index(), Ok(0b010));
+        assert_eq!((min + RelChunkPos::Y).try_as_morton_index(), Ok(0b100));
+        assert_eq!(min.try_as_hilbert_index(), Ok(0));
+        let too_far = max + RelChunkPos::Y;
+        assert!(too_far.try_as_morton_index().is_err());
+        assert!(too_far.try_as_hilbert_index().is_err());
+        assert!(AbsChunkPos::try_from_morton_index(1 << 63).is_err());
+        assert!(AbsChunkPos::try_from_hilbert_index(1 << 63).is_err());
+        // Every index inside of an aligned 2x2x2 cube should be in a contiguous block
+        let cube = AbsChunkRange::from_corners(AbsChunkPos::ZERO, AbsChunkPos::ONE);
+        let mut hilbert: Vec<u64> = cube.iter_xzy().map(|c| c.try_as_hilbert_index().unwrap()).collect();
+        let mut morton: Vec<u64> = cube.iter_xzy().map(|c| c.try_as_morton_index().unwrap()).collect();
+        hilbert.sort_unstable();
+        morton.sort_unstable();
+        assert!(hilbert.windows(2).all(|w| w[1] == w[0] + 1));
+        assert!(morton.windows(2).all(|w| w[1] == w[0] + 1));
+    }
+
+    #[quickcheck]
+    fn chunk_morton_roundtrip(x: i32, y: i32, z: i32) -> bool {
+        let pos = AbsChunkPos::new(x >> 11, y >> 11, z >> 11);
+        AbsChunkPos::try_from_morton_index(pos.try_as_morton_index().unwrap()) == Ok(pos)
+    }
+
+    #[quickcheck]
+    fn chunk_hilbert_roundtrip(x: i32, y: i32, z: i32) -> bool {
+        let pos = AbsChunkPos::new(x >> 11, y >> 11, z >> 11);
+        AbsChunkPos::try_from_hilbert_index(pos.try_as_hilbert_index().unwrap()) == Ok(pos)
+    }
+
+    #[quickcheck]
+    fn chunk_hilbert_adjacency(index: u64) -> bool {
+        let index = index % CHUNK_INDEX_MASK;
+        let a = AbsChunkPos::try_from_hilbert_index(index).unwrap();
+        let b = AbsChunkPos::try_from_hilbert_index(index + 1).unwrap();
+        (b - a).abs().dot(IVec3::ONE) == 1
+    }
+
+    #[quickcheck]
+    fn split_join_roundtrip(x: i32, y: i32, z: i32) -> bool {
+        let pos = AbsBlockPos::new(x, y, z);
+        let (chunk, in_chunk) = pos.split_chunk();
+        AbsBlockPos::join_chunk(chunk, in_chunk) == pos && InChunkPos::try_from_ivec3(in_chunk.0).is_ok()
+    }
+
+    #[quickcheck]
+    fn world_vec3_roundtrip(x: i16, y: i16, z: i16) -> bool {
+        let pos = AbsBlockPos::new(x as i32, y as i32, z as i32);
+        AbsBlockPos::from_world_vec3(pos.to_world_vec3()) == pos
+            && AbsBlockPos::from_world_vec3(pos.center_world_vec3()) == pos
+    }
+}
@@ -2,8 +2,14 @@
 
 //! A library crate of the in-memory, on-disk and network representations of the game's core data.
 
+pub mod block_loader;
 pub mod chunk;
+pub mod chunk_codec;
 pub mod chunk_storage;
 pub mod coordinates;
+pub mod migration;
+pub mod region;
 pub mod registry;
+pub mod registry_tags;
 pub mod voxeltypes;
+pub mod world;
@@ -0,0 +1,394 @@
+//! Upgrades of saved data from older format versions, one version at a time.
+//!
+//! Every versioned format has a [`Migrations`] list, where each [`MigrationStep`] upgrades the raw data from version N to N+1.
+//! Loaders apply the steps in sequence to bring old data up to the current version before parsing it,
+//! so each format change only needs a single new step, and the parsers only need to know the current format.
+//!
+//! Every historical format version is pinned by golden files in `testdata/`, loaded by the tests of this module.
+use std::path::PathBuf;
+
+use ron::Value;
+use thiserror::Error;
+
+use crate::chunk_codec::{decode, CHUNK_FORMAT_VERSION, CHUNK_MAGIC};
+use crate::region::RegionError;
+use crate::world::{WorldSave, WorldSaveError, WORLD_META_VERSION};
+
+/// Possible errors from upgrading data to the current format version.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum MigrationError {
+    /// The data has no readable format version.
+    #[error("The {format} data has no format version")]
+    MissingVersion {
+        /// The name of the format.
+        format: &'static str,
+    },
+    /// The data has a format version with no upgrade path, e.g. one written by a newer version of the game.
+    #[error("Unsupported {format} format version {version}, expected {first_version} to {current_version}")]
+    UnsupportedVersion {
+        /// The name of the format.
+        format: &'static str,
+        /// The format version of the data.
+        version: u32,
+        /// The oldest supported format version.
+        first_version: u32,
+        /// The current format version.
+        current_version: u32,
+    },
+    /// A migration step rejected the data.
+    #[error("Could not upgrade the {format} data from format version {from}: {reason}")]
+    StepFailed {
+        /// The name of the format.
+        format: &'static str,
+        /// The format version the failed step upgrades from.
+        from: u32,
+        /// Description of the problem.
+        reason: String,
+    },
+}
+
+/// A single upgrade of data from format version `from_version` to `from_version + 1`.
+pub struct MigrationStep<Blob> {
+    /// The format version this step upgrades from.
+    pub from_version: u32,
+    /// Short description of the format change, for logs.
+    pub description: &'static str,
+    /// Upgrades the data in place, the format version stored in the data is updated afterwards by [`Migrations::migrate`].
+    pub upgrade: fn(&mut Blob) -> Result<(), String>,
+}
+
+/// The sequence of upgrades of a versioned format, from its oldest supported version to the current one.
+pub struct Migrations<'s, Blob> {
+    /// The name of the format, for error messages.
+    pub format: &'static str,
+    /// The oldest format version that can still be upgraded.
+    pub first_version: u32,
+    /// The upgrade steps, ordered by [`MigrationStep::from_version`] starting at [`Self::first_version`] without gaps.
+    pub steps: &'s [MigrationStep<Blob>],
+    /// Reads the format version stored in the data.
+    pub read_version: fn(&Blob) -> Option<u32>,
+    /// Overwrites the format version stored in the data.
+    pub write_version: fn(&mut Blob, u32),
+}
+
+impl<'s, Blob> Migrations<'s, Blob> {
+    /// The format version the steps upgrade to.
+    pub const fn current_version(&self) -> u32 {
+        self.first_version + self.steps.len() as u32
+    }
+
+    /// Upgrades the data in place to the current format version, returning the format version it had before.
+    pub fn migrate(&self, blob: &mut Blob) -> Result<u32, MigrationError> {
+        let original_version =
+            (self.read_version)(blob).ok_or(MigrationError::MissingVersion { format: self.format })?;
+        if !(self.first_version..=self.current_version()).contains(&original_version) {
+            return Err(MigrationError::UnsupportedVersion {
+                format: self.format,
+                version: original_version,
+                first_version: self.first_version,
+                current_version: self.current_version(),
+            });
+        }
+        let first_step = (original_version - self.first_version) as usize;
+        for (version, step) in (original_version..).zip(&self.steps[first_step..]) {
+            debug_assert_eq!(
+                step.from_version, version,
+                "{} migration steps out of order",
+                self.format
+            );
+            let step_failed = |reason| MigrationError::StepFailed {
+                format: self.format,
+                from: version,
+                reason,
+            };
+            (step.upgrade)(blob).map_err(step_failed)?;
+            (self.write_version)(blob, version + 1);
+        }
+        Ok(original_version)
+    }
+}
+
+/// Upgrades of [encoded chunks](crate::chunk_codec), including the header.
+pub const CHUNK_MIGRATIONS: Migrations<'static, Vec<u8>> = Migrations {
+    format: "chunk",
+    first_version: 1,
+    steps: &[MigrationStep {
+        from_version: 1,
+        description: "Add the compression kind and dictionary ID to the header",
+        upgrade: chunk_v1_add_compression,
+    }],
+    read_version: read_chunk_version,
+    write_version: write_chunk_version,
+};
+
+/// Upgrades of the [world metadata](crate::world::WorldMeta), parsed as an untyped RON map.
+pub const WORLD_META_MIGRATIONS: Migrations<'static, ron::Map> = Migrations {
+    format: "world metadata",
+    first_version: 1,
+    steps: &[],
+    read_version: read_world_meta_version,
+    write_version: write_world_meta_version,
+};
+
+#[allow(clippy::ptr_arg)] // Has to match the `Migrations` function signatures
+fn read_chunk_version(blob: &Vec<u8>) -> Option<u32> {
+    blob.get(..6)
+        .filter(|header| header[..4] == CHUNK_MAGIC)
+        .map(|header| u16::from_le_bytes([header[4], header[5]]) as u32)
+}
+
+#[allow(clippy::ptr_arg)]
+fn write_chunk_version(blob: &mut Vec<u8>, version: u32) {
+    blob[4..6].copy_from_slice(&(version as u16).to_le_bytes());
+}
+
+fn chunk_v1_add_compression(blob: &mut Vec<u8>) -> Result<(), String> {
+    // Uncompressed, no dictionary
+    blob.splice(6..6, [0, 0, 0, 0, 0]);
+    Ok(())
+}
+
+fn world_meta_version_key() -> Value {
+    Value::String("format_version".to_owned())
+}
+
+fn read_world_meta_version(map: &ron::Map) -> Option<u32> {
+    let key = world_meta_version_key();
+    match map.iter().find(|(k, _)| **k == key) {
+        Some((_, Value::Number(version))) => version.as_i64().and_then(|v| u32::try_from(v).ok()),
+        _ => None,
+    }
+}
+
+fn write_world_meta_version(map: &mut ron::Map, version: u32) {
+    map.insert(world_meta_version_key(), Value::Number((version as i64).into()));
+}
+
+const _: () = assert!(CHUNK_MIGRATIONS.current_version() == CHUNK_FORMAT_VERSION as u32);
+const _: () = assert!(WORLD_META_MIGRATIONS.current_version() == WORLD_META_VERSION);
+
+/// Summary of an [offline world upgrade](upgrade_world).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorldUpgradeReport {
+    /// The format version of the world metadata before the upgrade.
+    pub meta_version: u32,
+    /// Number of saved chunks checked.
+    pub chunks_checked: usize,
+    /// Number of chunks stored with an older format version, which were rewritten with the current one.
+    pub chunks_upgraded: usize,
+}
+
+/// Upgrades all the data of the world in the given directory to the current format versions, rewriting every outdated chunk.
+///
+/// The world is locked for the whole upgrade, which fails if the world is open elsewhere.
+/// Loading a world upgrades old data on the fly, so this is only needed to stop depending on the migration steps,
+/// e.g. before removing support for old format versions.
+pub fn upgrade_world(dir: impl Into<PathBuf>) -> Result<WorldUpgradeReport, WorldSaveError> {
+    let world = WorldSave::open(dir)?;
+    let mut report = WorldUpgradeReport {
+        meta_version: world.opened_version(),
+        ..Default::default()
+    };
+    for dimension in world.meta().dimensions.iter() {
+        let mut regions = world.region_store(dimension.name.as_ref())?;
+        for pos in regions.saved_chunks()? {
+            report.chunks_checked += 1;
+            let Some(data) = regions.load_chunk_data(pos)? else {
+                continue;
+            };
+            if (CHUNK_MIGRATIONS.read_version)(&data) == Some(CHUNK_MIGRATIONS.current_version()) {
+                continue;
+            }
+            let chunk = decode(&data).map_err(|source| RegionError::Decode {
+                path: regions.region_path(pos),
+                pos,
+                source,
+            })?;
+            regions.save_chunk(pos, &chunk)?;
+            report.chunks_upgraded += 1;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+    use crate::chunk::{BlockLight, Chunk};
+    use crate::chunk_codec::encode;
+    use crate::coordinates::{AbsBlockPos, AbsChunkPos, InChunkPos, InChunkRange, CHUNK_DIM3Z};
+    use crate::registry::{RegistryId, RegistryName, RegistrySnapshot};
+    use crate::voxeltypes::BlockId;
+    use crate::world::{WorldMeta, WORLD_META_FILE};
+
+    /// The chunk stored in all the golden chunk files.
+    fn golden_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for i in 0..CHUNK_DIM3Z {
+            let block = match i {
+                _ if i % 97 == 0 => 5,
+                0..=16383 => 2,
+                _ => 0,
+            };
+            chunk.set_block(InChunkPos::try_from_index(i).unwrap(), BlockId::from_raw(block));
+        }
+        chunk.fill_light(InChunkRange::WHOLE_CHUNK, BlockLight::MAX);
+        chunk
+    }
+
+    /// The metadata stored in all the golden world metadata files.
+    fn golden_meta() -> WorldMeta {
+        let snapshot = RegistrySnapshot::from_entries([
+            (RegistryId::try_from(1).unwrap(), "air".parse().unwrap()),
+            (RegistryId::try_from(2).unwrap(), "stone".parse().unwrap()),
+            (RegistryId::try_from(5).unwrap(), "mod:ore".parse().unwrap()),
+        ])
+        .unwrap();
+        let mut meta = WorldMeta::new(0x5eed, snapshot, "overworld".parse().unwrap());
+        meta.spawn.position = AbsBlockPos::new(8, 70, -8);
+        meta.game_time = 123456;
+        meta
+    }
+
+    fn golden_file(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name);
+        std::fs::read(&path).unwrap_or_else(|e| panic!("Could not read golden file {}: {e}", path.display()))
+    }
+
+    #[test]
+    fn migration_steps() {
+        for (i, step) in CHUNK_MIGRATIONS.steps.iter().enumerate() {
+            assert_eq!(step.from_version, CHUNK_MIGRATIONS.first_version + i as u32);
+        }
+        for (i, step) in WORLD_META_MIGRATIONS.steps.iter().enumerate() {
+            assert_eq!(step.from_version, WORLD_META_MIGRATIONS.first_version + i as u32);
+        }
+
+        let current = encode(&golden_chunk());
+        let mut v1 = golden_file("chunk_v1.gsck");
+        assert_eq!(CHUNK_MIGRATIONS.migrate(&mut v1), Ok(1));
+        assert_eq!(v1, current);
+        let mut v2 = current.clone();
+        assert_eq!(CHUNK_MIGRATIONS.migrate(&mut v2), Ok(2));
+        assert_eq!(v2, current);
+
+        let mut bad = b"GSCK".to_vec();
+        assert_eq!(
+            CHUNK_MIGRATIONS.migrate(&mut bad),
+            Err(MigrationError::MissingVersion { format: "chunk" })
+        );
+        bad.extend_from_slice(&[9, 0]);
+        assert_eq!(
+            CHUNK_MIGRATIONS.migrate(&mut bad),
+            Err(MigrationError::UnsupportedVersion {
+                format: "chunk",
+                version: 9,
+                first_version: 1,
+                current_version: CHUNK_FORMAT_VERSION as u32,
+            })
+        );
+
+        fn fail(_: &mut Vec<u8>) -> Result<(), String> {
+            Err("broken".to_owned())
+        }
+        let failing = Migrations {
+            steps: &[
+                MigrationStep {
+                    from_version: 1,
+                    description: "Add the compression kind and dictionary ID to the header",
+                    upgrade: chunk_v1_add_compression,
+                },
+                MigrationStep {
+                    from_version: 2,
+                    description: "Fail",
+                    upgrade: fail,
+                },
+            ],
+            ..CHUNK_MIGRATIONS
+        };
+        let mut v1 = golden_file("chunk_v1.gsck");
+        assert_eq!(
+            failing.migrate(&mut v1),
+            Err(MigrationError::StepFailed {
+                format: "chunk",
+                from: 2,
+                reason: "broken".to_owned(),
+            })
+        );
+        // Steps before the failing one were applied
+        assert_eq!(v1, current);
+    }
+
+    #[test]
+    fn golden_chunks() {
+        let chunk = golden_chunk();
+        for (file, version) in [("chunk_v1.gsck", 1), ("chunk_v2.gsck", 2), ("chunk_v2_zstd.gsck", 2)] {
+            let data = golden_file(file);
+            assert_eq!((CHUNK_MIGRATIONS.read_version)(&data), Some(version), "{file}");
+            assert!(decode(&data).unwrap() == chunk, "{file}");
+        }
+        // Changes to the encoding need a new format version and golden file
+        let latest = format!("chunk_v{CHUNK_FORMAT_VERSION}.gsck");
+        assert!(
+            encode(&chunk) == golden_file(&latest),
+            "Encoding doesn't match {latest}"
+        );
+    }
+
+    #[test]
+    fn golden_world_meta() {
+        let meta = golden_meta();
+        let path = Path::new("world_meta_v1.ron");
+        let source = String::from_utf8(golden_file("world_meta_v1.ron")).unwrap();
+        assert_eq!(WorldMeta::from_ron_upgrading(path, &source).unwrap(), (meta.clone(), 1));
+        let latest = format!("world_meta_v{WORLD_META_VERSION}.ron");
+        assert_eq!(
+            meta.to_ron().as_bytes(),
+            golden_file(&latest),
+            "Serialization doesn't match {latest}"
+        );
+    }
+
+    #[test]
+    fn world_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let world = WorldSave::create(dir.path(), golden_meta()).unwrap();
+        let overworld: RegistryName = "overworld".parse().unwrap();
+        let mut regions = world.region_store(overworld.as_ref()).unwrap();
+        let old_positions = [AbsChunkPos::new(0, 0, 0), AbsChunkPos::new(-20, 3, 40)];
+        for pos in old_positions {
+            regions.save_chunk_data(pos, &golden_file("chunk_v1.gsck")).unwrap();
+        }
+        regions.save_chunk(AbsChunkPos::new(1, 0, 0), &golden_chunk()).unwrap();
+        drop(regions);
+
+        assert!(matches!(upgrade_world(dir.path()), Err(WorldSaveError::Locked { .. })));
+        drop(world);
+        let report = upgrade_world(dir.path()).unwrap();
+        assert_eq!(
+            report,
+            WorldUpgradeReport {
+                meta_version: 1,
+                chunks_checked: 3,
+                chunks_upgraded: 2,
+            }
+        );
+
+        let world = WorldSave::open(dir.path()).unwrap();
+        let mut regions = world.region_store(world.meta().spawn.dimension.as_ref()).unwrap();
+        for pos in old_positions {
+            let data = regions.load_chunk_data(pos).unwrap().unwrap();
+            assert_eq!(
+                (CHUNK_MIGRATIONS.read_version)(&data),
+                Some(CHUNK_FORMAT_VERSION as u32)
+            );
+            assert!(decode(&data).unwrap() == golden_chunk());
+        }
+        drop(regions);
+        drop(world);
+        assert_eq!(upgrade_world(dir.path()).unwrap().chunks_upgraded, 0);
+        assert!(!dir.path().join(format!("{WORLD_META_FILE}.v1.bak")).exists());
+    }
+}
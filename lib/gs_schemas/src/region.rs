@@ -0,0 +1,769 @@
+//! Storage of chunks on disk in region files, each holding a cube of [`REGION_DIM`]³ chunks.
+//!
+//! All integers are little-endian. A region file starts with a header of [`REGION_HEADER_SECTORS`] sectors, containing:
+//! - `b"GSRG"` magic bytes, `u16` format version and 2 bytes of padding
+//! - the journal entry of the last offset table update: `u32` table slot, `u32` first sector, `u32` length and `u32` checksum
+//! - the offset table, with the `u32` first sector and `u32` byte length of every chunk in the region in XZY order,
+//!   where a first sector of 0 marks a missing chunk
+//!
+//! The header is followed by the chunks, encoded with [`chunk_codec`](crate::chunk_codec) into contiguous runs of
+//! [`REGION_SECTOR_SIZE`]-byte sectors. Sectors freed by overwritten or deleted chunks are reused by later writes.
+//!
+//! Updates never overwrite data that is still referenced: a saved chunk is written to free sectors, then the journal entry
+//! is written, and only then the offset table entry, with the file synced between the steps.
+//! If a crash interrupts the table entry write, the journal entry is replayed when the file is opened again.
+//! An interrupted journal write fails its checksum and is ignored, which leaves the previous version of the chunk in place.
+//! New region files are fully written under a temporary name and then renamed, so they're never partially created.
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy_math::IVec3;
+use bitvec::prelude::*;
+use hashbrown::HashMap;
+use thiserror::Error;
+
+use crate::chunk::Chunk;
+use crate::chunk_codec::{
+    decode_with_dictionary, encode_with, ChunkCompression, ChunkDecodeError, ChunkDictionary, ChunkEncodeOptions,
+};
+use crate::coordinates::AbsChunkPos;
+
+/// Number of chunks along each axis of a region.
+pub const REGION_DIM: i32 = 16;
+/// Number of chunks in a region.
+pub const REGION_DIM3: usize = (REGION_DIM * REGION_DIM * REGION_DIM) as usize;
+/// Size of the allocation unit of region files in bytes.
+pub const REGION_SECTOR_SIZE: u64 = 4096;
+/// Magic bytes at the start of every region file.
+pub const REGION_MAGIC: [u8; 4] = *b"GSRG";
+/// The region file format version.
+pub const REGION_FORMAT_VERSION: u16 = 1;
+/// Number of sectors at the start of region files taken up by the header.
+pub const REGION_HEADER_SECTORS: u32 = (HEADER_LEN as u64).div_ceil(REGION_SECTOR_SIZE) as u32;
+
+const JOURNAL_OFFSET: usize = 8;
+const TABLE_OFFSET: usize = JOURNAL_OFFSET + 16;
+const HEADER_LEN: usize = TABLE_OFFSET + 8 * REGION_DIM3;
+/// File name extension of region files.
+const REGION_EXTENSION: &str = "gsr";
+/// Maximum number of region files a [`RegionStore`] keeps open, to stay well below the open file limits of the OS.
+const MAX_OPEN_REGIONS: usize = 64;
+
+/// Possible errors from reading and writing region files.
+#[derive(Debug, Error)]
+pub enum RegionError {
+    /// The region file or directory could not be accessed.
+    #[error("I/O error accessing region data at {}: {source}", path.display())]
+    Io {
+        /// The path of the region file or directory.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The region file header is invalid, e.g. it's not a region file or chunks overlap.
+    #[error("Region file {} is corrupted: {reason}", path.display())]
+    Corrupted {
+        /// The path of the region file.
+        path: PathBuf,
+        /// Description of the problem.
+        reason: String,
+    },
+    /// Empty chunk data can't be stored, region files use a length of 0 to mark missing chunks.
+    #[error("Can't save empty data for chunk {0:?}")]
+    EmptyChunkData(AbsChunkPos),
+    /// A chunk stored in the region file could not be decoded.
+    #[error("Could not decode chunk {pos:?} from region file {}: {source}", path.display())]
+    Decode {
+        /// The path of the region file.
+        path: PathBuf,
+        /// The position of the chunk.
+        pos: AbsChunkPos,
+        /// The decoding error.
+        source: ChunkDecodeError,
+    },
+}
+
+/// Location of a chunk inside of a region file.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct RegionEntry {
+    /// First sector of the chunk data, 0 if the chunk is missing.
+    sector: u32,
+    /// Length of the chunk data in bytes.
+    len: u32,
+}
+
+impl RegionEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            sector: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            len: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[0..4].copy_from_slice(&self.sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    fn is_present(self) -> bool {
+        self.sector != 0
+    }
+
+    fn sectors(self) -> Range<usize> {
+        let start = self.sector as usize;
+        start..start + sectors_for_len(self.len as usize)
+    }
+}
+
+fn sectors_for_len(len: usize) -> usize {
+    len.div_ceil(REGION_SECTOR_SIZE as usize)
+}
+
+/// FNV-1a hash protecting the journal entry from torn writes.
+fn journal_checksum(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0x811c9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+/// An open region file, with its offset table cached in memory.
+struct RegionFile {
+    path: PathBuf,
+    file: File,
+    table: Vec<RegionEntry>,
+    used_sectors: BitVec,
+}
+
+impl RegionFile {
+    /// Opens the region file, replaying the journal if needed. Returns [`None`] if it doesn't exist and `create` is false.
+    fn open(path: PathBuf, create: bool) -> Result<Option<Self>, RegionError> {
+        let io_error = |source| RegionError::Io {
+            path: path.clone(),
+            source,
+        };
+        let corrupted = |reason: String| RegionError::Corrupted {
+            path: path.clone(),
+            reason,
+        };
+        if !path.try_exists().map_err(io_error)? {
+            if !create {
+                return Ok(None);
+            }
+            Self::create(&path).map_err(io_error)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(io_error)?;
+        let file_len = file.metadata().map_err(io_error)?.len();
+        let mut header = vec![0u8; HEADER_LEN];
+        match file.read_exact(&mut header) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(corrupted("Truncated header".to_owned())),
+            result => result.map_err(io_error)?,
+        }
+        if header[0..4] != REGION_MAGIC {
+            return Err(corrupted("Not a region file, the magic bytes don't match".to_owned()));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != REGION_FORMAT_VERSION {
+            return Err(corrupted(format!("Unsupported region format version {version}")));
+        }
+        let mut table: Vec<RegionEntry> = header[TABLE_OFFSET..]
+            .chunks_exact(8)
+            .map(RegionEntry::from_bytes)
+            .collect();
+
+        let journal = &header[JOURNAL_OFFSET..TABLE_OFFSET];
+        let journal_slot = u32::from_le_bytes(journal[0..4].try_into().unwrap()) as usize;
+        let journal_valid =
+            journal_checksum(&journal[0..12]) == u32::from_le_bytes(journal[12..16].try_into().unwrap());
+        if journal_valid && journal_slot < REGION_DIM3 {
+            let entry = RegionEntry::from_bytes(&journal[4..12]);
+            if table[journal_slot] != entry {
+                table[journal_slot] = entry;
+                write_at(&mut file, (TABLE_OFFSET + 8 * journal_slot) as u64, &entry.to_bytes())
+                    .and_then(|_| file.sync_data())
+                    .map_err(io_error)?;
+            }
+        }
+
+        let mut used_sectors = bitvec![1; REGION_HEADER_SECTORS as usize];
+        for (slot, entry) in table.iter().enumerate().filter(|(_, entry)| entry.is_present()) {
+            let sectors = entry.sectors();
+            if entry.sector < REGION_HEADER_SECTORS
+                || entry.len == 0
+                || entry.sector as u64 * REGION_SECTOR_SIZE + entry.len as u64 > file_len
+            {
+                return Err(corrupted(format!("Invalid location of the chunk in slot {slot}")));
+            }
+            if used_sectors.len() < sectors.end {
+                used_sectors.resize(sectors.end, false);
+            }
+            if used_sectors[sectors.clone()].any() {
+                return Err(corrupted(format!("The chunk in slot {slot} overlaps another chunk")));
+            }
+            used_sectors[sectors].fill(true);
+        }
+
+        Ok(Some(Self {
+            path,
+            file,
+            table,
+            used_sectors,
+        }))
+    }
+
+    /// Atomically creates an empty region file.
+    fn create(path: &Path) -> std::io::Result<()> {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[0..4].copy_from_slice(&REGION_MAGIC);
+        header[4..6].copy_from_slice(&REGION_FORMAT_VERSION.to_le_bytes());
+        let temp_path = path.with_extension(format!("{REGION_EXTENSION}.tmp"));
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&header)?;
+        temp.sync_all()?;
+        drop(temp);
+        std::fs::rename(&temp_path, path)?;
+        sync_parent_dir(path)
+    }
+
+    fn read(&mut self, slot: usize) -> std::io::Result<Option<Vec<u8>>> {
+        let entry = self.table[slot];
+        if !entry.is_present() {
+            return Ok(None);
+        }
+        let mut data = vec![0; entry.len as usize];
+        self.file
+            .seek(SeekFrom::Start(entry.sector as u64 * REGION_SECTOR_SIZE))?;
+        self.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Replaces or removes the chunk data in the given slot, see the [module documentation](self) for the update order.
+    fn write(&mut self, slot: usize, data: Option<&[u8]>) -> std::io::Result<()> {
+        let entry = match data {
+            Some(data) => {
+                let sector = self.allocate(sectors_for_len(data.len()));
+                write_at(&mut self.file, sector as u64 * REGION_SECTOR_SIZE, data)?;
+                self.file.sync_data()?;
+                RegionEntry {
+                    sector,
+                    len: data.len() as u32,
+                }
+            }
+            None => RegionEntry::default(),
+        };
+
+        let mut journal = [0u8; 16];
+        journal[0..4].copy_from_slice(&(slot as u32).to_le_bytes());
+        journal[4..12].copy_from_slice(&entry.to_bytes());
+        let checksum = journal_checksum(&journal[0..12]);
+        journal[12..16].copy_from_slice(&checksum.to_le_bytes());
+        write_at(&mut self.file, JOURNAL_OFFSET as u64, &journal)?;
+        self.file.sync_data()?;
+        write_at(&mut self.file, (TABLE_OFFSET + 8 * slot) as u64, &entry.to_bytes())?;
+        self.file.sync_data()?;
+
+        let old_entry = std::mem::replace(&mut self.table[slot], entry);
+        if old_entry.is_present() {
+            self.used_sectors[old_entry.sectors()].fill(false);
+        }
+        Ok(())
+    }
+
+    /// Finds the first run of free sectors of the given length, extending the file if needed, and marks it as used.
+    fn allocate(&mut self, sector_count: usize) -> u32 {
+        let mut run_start = REGION_HEADER_SECTORS as usize;
+        let mut run_len = 0;
+        for (sector, used) in self.used_sectors.iter().by_vals().enumerate().skip(run_start) {
+            if used {
+                run_start = sector + 1;
+                run_len = 0;
+            } else {
+                run_len += 1;
+                if run_len == sector_count {
+                    break;
+                }
+            }
+        }
+        let run_end = run_start + sector_count;
+        if self.used_sectors.len() < run_end {
+            self.used_sectors.resize(run_end, false);
+        }
+        self.used_sectors[run_start..run_end].fill(true);
+        run_start as u32
+    }
+}
+
+/// Syncs the directory entry of a newly created or renamed file, so that the file doesn't disappear after a crash.
+#[cfg(unix)]
+pub(crate) fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    File::open(parent)?.sync_all()
+}
+
+/// Directories can't be opened as files on other platforms, where renames are durable once they return.
+#[cfg(not(unix))]
+pub(crate) fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn write_at(file: &mut File, offset: u64, data: &[u8]) -> std::io::Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(data)
+}
+
+/// A directory of region files storing the chunks of a world, see the [module documentation](self) for the file format.
+///
+/// Region files are opened on first access and kept open until the store is dropped, or until the number of open files
+/// exceeds a limit and the region farthest away from the newly opened one is closed.
+/// Every write is synced to disk before returning.
+pub struct RegionStore {
+    dir: PathBuf,
+    compression: ChunkCompression,
+    dictionary: Option<Arc<ChunkDictionary>>,
+    regions: HashMap<IVec3, RegionFile>,
+}
+
+impl RegionStore {
+    /// Opens the store in the given directory, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, RegionError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|source| RegionError::Io {
+            path: dir.clone(),
+            source,
+        })?;
+        Ok(Self {
+            dir,
+            compression: ChunkEncodeOptions::DISK.compression,
+            dictionary: None,
+            regions: HashMap::new(),
+        })
+    }
+
+    /// Sets the compression used for newly saved chunks, any compression can be loaded.
+    pub fn with_compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the dictionary used for compressing newly saved chunks and for loading chunks compressed with it.
+    /// Chunks saved without a dictionary can still be loaded.
+    pub fn with_dictionary(mut self, dictionary: Arc<ChunkDictionary>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// The directory containing the region files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The position of the region containing the given chunk, and the slot of the chunk in the region.
+    pub fn region_of(pos: AbsChunkPos) -> (IVec3, usize) {
+        let region = pos.into_ivec3().div_euclid(IVec3::splat(REGION_DIM));
+        let local = pos.into_ivec3().rem_euclid(IVec3::splat(REGION_DIM));
+        let slot = local.x + REGION_DIM * local.z + REGION_DIM * REGION_DIM * local.y;
+        (region, slot as usize)
+    }
+
+    /// The path of the region file containing the given chunk.
+    pub fn region_path(&self, pos: AbsChunkPos) -> PathBuf {
+        let (region, _) = Self::region_of(pos);
+        self.dir
+            .join(format!("r.{}.{}.{}.{REGION_EXTENSION}", region.x, region.y, region.z))
+    }
+
+    fn region(&mut self, pos: AbsChunkPos, create: bool) -> Result<Option<(&mut RegionFile, usize)>, RegionError> {
+        let (region, slot) = Self::region_of(pos);
+        if !self.regions.contains_key(&region) {
+            let Some(file) = RegionFile::open(self.region_path(pos), create)? else {
+                return Ok(None);
+            };
+            if self.regions.len() >= MAX_OPEN_REGIONS {
+                let farthest = *self
+                    .regions
+                    .keys()
+                    .max_by_key(|&&open| (open - region).abs().max_element())
+                    .expect("The limit is above 0");
+                self.regions.remove(&farthest);
+            }
+            self.regions.insert(region, file);
+        }
+        Ok(self.regions.get_mut(&region).map(|file| (file, slot)))
+    }
+
+    /// Loads the chunk at the given position, or returns [`None`] if it was never saved or was deleted.
+    pub fn load_chunk(&mut self, pos: AbsChunkPos) -> Result<Option<Chunk>, RegionError> {
+        let Some(data) = self.load_chunk_data(pos)? else {
+            return Ok(None);
+        };
+        decode_with_dictionary(&data, self.dictionary.as_deref())
+            .map(Some)
+            .map_err(|source| RegionError::Decode {
+                path: self.region_path(pos),
+                pos,
+                source,
+            })
+    }
+
+    /// Loads the encoded data of the chunk at the given position without decoding it.
+    pub fn load_chunk_data(&mut self, pos: AbsChunkPos) -> Result<Option<Vec<u8>>, RegionError> {
+        let Some((region, slot)) = self.region(pos, false)? else {
+            return Ok(None);
+        };
+        region.read(slot).map_err(|source| RegionError::Io {
+            path: region.path.clone(),
+            source,
+        })
+    }
+
+    /// Lists the positions of all the chunks saved in the store, reading every region file in the directory.
+    /// Region files that were not open already are closed again after reading them.
+    pub fn saved_chunks(&mut self) -> Result<Vec<AbsChunkPos>, RegionError> {
+        let io_error = |source| RegionError::Io {
+            path: self.dir.clone(),
+            source,
+        };
+        let mut regions = Vec::new();
+        for dir_entry in std::fs::read_dir(&self.dir).map_err(io_error)? {
+            let file_name = dir_entry.map_err(io_error)?.file_name();
+            let Some(coords) = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix("r."))
+                .and_then(|name| name.strip_suffix(REGION_EXTENSION))
+                .and_then(|name| name.strip_suffix('.'))
+            else {
+                continue;
+            };
+            let coords: Vec<i32> = coords.split('.').filter_map(|c| c.parse().ok()).collect();
+            if let &[x, y, z] = coords.as_slice() {
+                regions.push(IVec3::new(x, y, z));
+            }
+        }
+        regions.sort_unstable_by_key(|region| region.to_array());
+
+        let mut chunks = Vec::new();
+        for region in regions {
+            let origin = AbsChunkPos::from(region * REGION_DIM);
+            let opened;
+            let file = match self.regions.get(&region) {
+                Some(file) => file,
+                None => match RegionFile::open(self.region_path(origin), false)? {
+                    Some(file) => {
+                        opened = file;
+                        &opened
+                    }
+                    None => continue,
+                },
+            };
+            for (slot, entry) in file.table.iter().enumerate() {
+                if entry.is_present() {
+                    let slot = slot as i32;
+                    let local = IVec3::new(
+                        slot % REGION_DIM,
+                        slot / (REGION_DIM * REGION_DIM),
+                        slot / REGION_DIM % REGION_DIM,
+                    );
+                    chunks.push(AbsChunkPos::from(*origin + local));
+                }
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Saves the chunk at the given position, replacing any previously saved version.
+    pub fn save_chunk(&mut self, pos: AbsChunkPos, chunk: &Chunk) -> Result<(), RegionError> {
+        let data = encode_with(
+            chunk,
+            ChunkEncodeOptions {
+                compression: self.compression,
+                dictionary: self.dictionary.as_deref(),
+            },
+        );
+        self.save_chunk_data(pos, &data)
+    }
+
+    /// Saves already encoded chunk data at the given position, replacing any previously saved version.
+    /// The data has to be encoded without a dictionary or with the [dictionary of the store](Self::with_dictionary) for the chunk to be loadable.
+    /// Empty data is rejected without touching the region file.
+    pub fn save_chunk_data(&mut self, pos: AbsChunkPos, data: &[u8]) -> Result<(), RegionError> {
+        if data.is_empty() {
+            return Err(RegionError::EmptyChunkData(pos));
+        }
+        let (region, slot) = self.region(pos, true)?.expect("Region files are created when saving");
+        region.write(slot, Some(data)).map_err(|source| RegionError::Io {
+            path: region.path.clone(),
+            source,
+        })
+    }
+
+    /// Deletes the chunk at the given position, returning whether it was saved before.
+    pub fn delete_chunk(&mut self, pos: AbsChunkPos) -> Result<bool, RegionError> {
+        let Some((region, slot)) = self.region(pos, false)? else {
+            return Ok(false);
+        };
+        if !region.table[slot].is_present() {
+            return Ok(false);
+        }
+        region.write(slot, None).map_err(|source| RegionError::Io {
+            path: region.path.clone(),
+            source,
+        })?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::chunk::BlockLight;
+    use crate::chunk_codec::decode;
+    use crate::coordinates::{InChunkPos, InChunkRange, CHUNK_DIM3Z};
+    use crate::voxeltypes::BlockId;
+
+    /// A chunk which takes up multiple sectors when stored without compression.
+    fn varied_chunk(seed: u64) -> Chunk {
+        let mut chunk = Chunk::new();
+        for i in 0..CHUNK_DIM3Z {
+            let block = BlockId::from_raw((i as u64 * 7 + seed) % 37);
+            chunk.set_block(InChunkPos::try_from_index(i).unwrap(), block);
+        }
+        chunk
+    }
+
+    fn file_len(path: &Path) -> u64 {
+        std::fs::metadata(path).unwrap().len()
+    }
+
+    #[test]
+    fn save_load_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegionStore::open(dir.path().join("regions")).unwrap();
+        let positions = [
+            AbsChunkPos::new(0, 0, 0),
+            AbsChunkPos::new(15, 15, 15),
+            AbsChunkPos::new(-1, 0, 0),
+            AbsChunkPos::new(3, -40, 100),
+        ];
+        assert_eq!(RegionStore::region_of(positions[0]), (IVec3::ZERO, 0));
+        assert_eq!(RegionStore::region_of(positions[1]), (IVec3::ZERO, REGION_DIM3 - 1));
+        assert_eq!(RegionStore::region_of(positions[2]), (IVec3::new(-1, 0, 0), 15));
+        assert!(store.region_path(positions[3]).ends_with("r.0.-3.6.gsr"));
+
+        assert!(store.load_chunk(positions[0]).unwrap().is_none());
+        assert!(!store.delete_chunk(positions[0]).unwrap());
+        assert!(!store.region_path(positions[0]).exists());
+
+        let mut lit = Chunk::new_filled(BlockId::from_raw(3));
+        lit.fill_light(InChunkRange::WHOLE_CHUNK, BlockLight::MAX);
+        lit.set_light(InChunkPos::ONE, BlockLight::ZERO);
+        let chunks = [varied_chunk(0), varied_chunk(1), Chunk::new(), lit];
+        for (&pos, chunk) in positions.iter().zip(chunks.iter()) {
+            store.save_chunk(pos, chunk).unwrap();
+        }
+        for (&pos, chunk) in positions.iter().zip(chunks.iter()) {
+            assert_eq!(store.load_chunk(pos).unwrap().as_ref(), Some(chunk));
+        }
+        assert!(store.load_chunk(AbsChunkPos::new(1, 0, 0)).unwrap().is_none());
+
+        assert!(store.delete_chunk(positions[1]).unwrap());
+        assert!(!store.delete_chunk(positions[1]).unwrap());
+        assert!(store.load_chunk(positions[1]).unwrap().is_none());
+
+        // Everything is on disk when reopened
+        drop(store);
+        let mut store = RegionStore::open(dir.path().join("regions")).unwrap();
+        assert_eq!(store.load_chunk(positions[0]).unwrap().as_ref(), Some(&chunks[0]));
+        assert!(store.load_chunk(positions[1]).unwrap().is_none());
+        assert_eq!(store.load_chunk(positions[3]).unwrap().as_ref(), Some(&chunks[3]));
+        assert_eq!(
+            store.saved_chunks().unwrap(),
+            [positions[2], positions[3], positions[0]]
+        );
+        let raw = store.load_chunk_data(positions[0]).unwrap().unwrap();
+        assert_eq!(decode(&raw).unwrap(), chunks[0]);
+
+        // Empty data would be read back as a corrupted entry
+        assert!(matches!(
+            store.save_chunk_data(positions[0], &[]),
+            Err(RegionError::EmptyChunkData(pos)) if pos == positions[0]
+        ));
+        assert!(matches!(
+            store.save_chunk_data(AbsChunkPos::new(100, 0, 0), &[]),
+            Err(RegionError::EmptyChunkData(_))
+        ));
+        drop(store);
+        let mut store = RegionStore::open(dir.path().join("regions")).unwrap();
+        assert_eq!(store.load_chunk(positions[0]).unwrap().as_ref(), Some(&chunks[0]));
+        assert!(!store.region_path(AbsChunkPos::new(100, 0, 0)).exists());
+    }
+
+    #[test]
+    fn dictionary_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let samples: Vec<Chunk> = (0..64).map(varied_chunk).collect();
+        let dictionary = ChunkDictionary::train(NonZeroU32::new(3).unwrap(), samples.iter(), 4096).unwrap();
+        let dictionary = Arc::new(dictionary);
+        let (a, b) = (AbsChunkPos::new(0, 0, 0), AbsChunkPos::new(1, 0, 0));
+
+        let mut plain = RegionStore::open(dir.path()).unwrap();
+        plain.save_chunk(a, &samples[0]).unwrap();
+        drop(plain);
+        let mut store = RegionStore::open(dir.path())
+            .unwrap()
+            .with_dictionary(dictionary.clone());
+        store.save_chunk(b, &samples[1]).unwrap();
+        let raw = store.load_chunk_data(b).unwrap().unwrap();
+        assert_eq!(&raw[7..11], &3u32.to_le_bytes());
+        assert_eq!(store.load_chunk(a).unwrap().as_ref(), Some(&samples[0]));
+        assert_eq!(store.load_chunk(b).unwrap().as_ref(), Some(&samples[1]));
+        drop(store);
+
+        let mut plain = RegionStore::open(dir.path()).unwrap();
+        assert!(matches!(
+            plain.load_chunk(b),
+            Err(RegionError::Decode {
+                source: ChunkDecodeError::DictionaryMismatch { expected: 3, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn open_region_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        let positions: Vec<AbsChunkPos> = (0..MAX_OPEN_REGIONS as i32 + 8)
+            .map(|i| AbsChunkPos::new(i * REGION_DIM, 0, 0))
+            .collect();
+        for &pos in positions.iter() {
+            store.save_chunk(pos, &Chunk::new()).unwrap();
+        }
+        assert_eq!(store.regions.len(), MAX_OPEN_REGIONS);
+        // The regions farthest away from the newest one are closed first
+        assert!(!store.regions.contains_key(&IVec3::ZERO));
+        assert!(store
+            .regions
+            .contains_key(&RegionStore::region_of(*positions.last().unwrap()).0));
+        assert_eq!(store.saved_chunks().unwrap(), positions);
+        for &pos in positions.iter() {
+            assert!(store.load_chunk(pos).unwrap().is_some());
+        }
+        assert_eq!(store.regions.len(), MAX_OPEN_REGIONS);
+
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        assert_eq!(store.saved_chunks().unwrap(), positions);
+        assert!(store.regions.is_empty());
+    }
+
+    #[test]
+    fn sector_reuse() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegionStore::open(dir.path())
+            .unwrap()
+            .with_compression(ChunkCompression::None);
+        let (a, b) = (AbsChunkPos::new(0, 0, 0), AbsChunkPos::new(1, 0, 0));
+        let path = store.region_path(a);
+        let big = varied_chunk(0);
+        let big_sectors = sectors_for_len(crate::chunk_codec::encode(&big).len()) as u64;
+        assert!(big_sectors > 1);
+        let header_len = REGION_HEADER_SECTORS as u64 * REGION_SECTOR_SIZE;
+
+        store.save_chunk(a, &big).unwrap();
+        store.save_chunk(b, &Chunk::new()).unwrap();
+        let len_before = file_len(&path);
+        assert!(len_before > header_len + (big_sectors * REGION_SECTOR_SIZE));
+        // A smaller chunk fits in the space freed by the big one
+        store.save_chunk(a, &Chunk::new_filled(BlockId::from_raw(1))).unwrap();
+        store.save_chunk(b, &big).unwrap();
+        assert!(file_len(&path) <= header_len + (big_sectors + 2) * REGION_SECTOR_SIZE);
+        store.delete_chunk(b).unwrap();
+        store.save_chunk(AbsChunkPos::new(2, 0, 0), &varied_chunk(5)).unwrap();
+        assert!(file_len(&path) <= header_len + (big_sectors + 2) * REGION_SECTOR_SIZE);
+
+        drop(store);
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        assert_eq!(
+            store.load_chunk(a).unwrap(),
+            Some(Chunk::new_filled(BlockId::from_raw(1)))
+        );
+        assert_eq!(
+            store.load_chunk(AbsChunkPos::new(2, 0, 0)).unwrap(),
+            Some(varied_chunk(5))
+        );
+        // Reopening recomputes the free sectors
+        store.save_chunk(b, &big).unwrap();
+        assert!(file_len(&path) <= header_len + (2 * big_sectors + 2) * REGION_SECTOR_SIZE);
+    }
+
+    #[test]
+    fn crash_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        let pos = AbsChunkPos::new(0, 2, 0);
+        let (_, slot) = RegionStore::region_of(pos);
+        let path = store.region_path(pos);
+        store.save_chunk(pos, &varied_chunk(0)).unwrap();
+        store.save_chunk(pos, &varied_chunk(1)).unwrap();
+        drop(store);
+        let pristine = std::fs::read(&path).unwrap();
+        let patched = |offset: usize, bytes: &[u8]| {
+            let mut data = pristine.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            std::fs::write(&path, data).unwrap();
+            RegionStore::open(dir.path()).unwrap().load_chunk(pos)
+        };
+
+        // A torn offset table write is fixed by replaying the journal
+        let table_entry = TABLE_OFFSET + 8 * slot;
+        assert_eq!(patched(table_entry, &[0xFF, 0xFF]).unwrap(), Some(varied_chunk(1)));
+        assert_eq!(patched(table_entry, &[0; 8]).unwrap(), Some(varied_chunk(1)));
+        // The replayed entry is written back
+        let mut store = RegionStore::open(dir.path()).unwrap();
+        assert_eq!(store.load_chunk(pos).unwrap(), Some(varied_chunk(1)));
+        drop(store);
+        assert_eq!(std::fs::read(&path).unwrap(), pristine);
+
+        // A torn journal write is ignored
+        assert_eq!(patched(JOURNAL_OFFSET + 6, &[0xAB]).unwrap(), Some(varied_chunk(1)));
+
+        let corrupted = |offset: usize, bytes: &[u8]| {
+            let mut data = pristine.clone();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            // Invalidate the journal, so it can't fix the corruption
+            data[JOURNAL_OFFSET + 12] ^= 1;
+            std::fs::write(&path, data).unwrap();
+            let mut store = RegionStore::open(dir.path()).unwrap();
+            store.load_chunk(pos).unwrap_err()
+        };
+        assert!(matches!(corrupted(0, b"XXXX"), RegionError::Corrupted { .. }));
+        assert!(matches!(
+            corrupted(table_entry, &[1, 0, 0, 0]),
+            RegionError::Corrupted { .. }
+        ));
+        assert!(matches!(
+            corrupted(table_entry + 4, &[0xFF, 0xFF, 0xFF, 0]),
+            RegionError::Corrupted { .. }
+        ));
+        let data_start = u32::from_le_bytes(pristine[table_entry..table_entry + 4].try_into().unwrap()) as usize
+            * REGION_SECTOR_SIZE as usize;
+        assert!(matches!(corrupted(data_start, b"GSCX"), RegionError::Decode { .. }));
+
+        std::fs::write(&path, &pristine[..100]).unwrap();
+        let err = RegionStore::open(dir.path()).unwrap().load_chunk(pos).unwrap_err();
+        assert!(err.to_string().contains("Truncated"), "{err}");
+    }
+}
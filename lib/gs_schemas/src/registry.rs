@@ -1,10 +1,16 @@
 //! A data structure for keeping track of a stable mapping between: namespaced strings, numerical IDs and objects.
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::num::{NonZeroU32, TryFromIntError};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use bytemuck::{CheckedBitPattern, NoUninit, PodInOption, ZeroableInOption};
 use hashbrown::{Equivalent, HashMap};
 use kstring::{KString, KStringRef};
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 /// Default namespace for the Geosia's objects
@@ -13,7 +19,12 @@ pub static GEOSIA_REGISTRY_DOMAIN: &str = "gs";
 pub static GEOSIA_REGISTRY_DOMAIN_KS: KString = KString::from_static(GEOSIA_REGISTRY_DOMAIN);
 
 /// Simple namespaced registry object name
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Default, Hash, Serialize, Deserialize)]
+///
+/// Serialized as the compact `ns:key` string in human-readable formats, and as a struct otherwise.
+/// Deserialization accepts both forms, and validates the name.
+/// Names that were accepted before the validation was introduced, such as ones with uppercase letters, are now rejected,
+/// so data containing them fails to deserialize instead of being silently renamed.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Default, Hash)]
 pub struct RegistryName {
     /// The namespace
     pub ns: KString,
@@ -31,33 +42,191 @@ pub struct RegistryNameRef<'n> {
 }
 
 impl RegistryName {
-    /// Constructs a `gs:`-namespaced name
+    /// Constructs a `gs:`-namespaced name, the key is only validated in debug builds.
     pub fn geosia(key: impl Into<KString>) -> Self {
-        Self {
+        let name = Self {
             ns: GEOSIA_REGISTRY_DOMAIN_KS.clone(),
             key: key.into(),
-        }
+        };
+        debug_assert!(name.validate().is_ok(), "Invalid registry name {name}");
+        name
     }
 
     /// Converts the name to a reference struct
     pub fn as_ref(&self) -> RegistryNameRef {
         self.into()
     }
+
+    /// Parses a `ns:key` name, requiring an explicit namespace.
+    /// Use [`FromStr`] to default to the `gs` namespace instead.
+    pub fn parse_qualified(name: &str) -> Result<Self, RegistryNameParseError> {
+        let (ns, key) = name
+            .split_once(':')
+            .ok_or_else(|| RegistryNameParseError::MissingColon(name.to_owned()))?;
+        Self::from_parts(name, ns, key, ns.len() + 1)
+    }
+
+    /// Checks that both parts of the name are non-empty and only use the allowed characters, `[a-z0-9_./-]`.
+    pub fn validate(&self) -> Result<(), RegistryNameParseError> {
+        self.as_ref().validate()
+    }
+
+    fn from_parts(name: &str, ns: &str, key: &str, key_offset: usize) -> Result<Self, RegistryNameParseError> {
+        validate_registry_name_parts(name, ns, key, key_offset)?;
+        Ok(Self {
+            ns: if ns == GEOSIA_REGISTRY_DOMAIN {
+                GEOSIA_REGISTRY_DOMAIN_KS.clone()
+            } else {
+                KString::from_ref(ns)
+            },
+            key: KString::from_ref(key),
+        })
+    }
 }
 
 impl<'a> RegistryNameRef<'a> {
-    /// Constructs a `gs:`-namespaced name reference
+    /// Constructs a `gs:`-namespaced name reference, the key is only validated in debug builds.
     pub fn geosia(key: impl Into<KStringRef<'a>>) -> Self {
-        Self {
+        let name = Self {
             ns: KStringRef::from(&GEOSIA_REGISTRY_DOMAIN_KS),
             key: key.into(),
-        }
+        };
+        debug_assert!(name.validate().is_ok(), "Invalid registry name {name}");
+        name
     }
 
     /// Converts the name to an owned struct, copying the strings as needed
     pub fn to_owned(&self) -> RegistryName {
         self.into()
     }
+
+    /// Checks that both parts of the name are non-empty and only use the allowed characters, `[a-z0-9_./-]`.
+    pub fn validate(&self) -> Result<(), RegistryNameParseError> {
+        validate_registry_name_parts(self, &self.ns, &self.key, self.ns.len() + 1)
+    }
+}
+
+/// Validates the parts of a name, `key_offset` is the byte position of the key in `name` for error reporting.
+fn validate_registry_name_parts(
+    name: impl Display,
+    ns: &str,
+    key: &str,
+    key_offset: usize,
+) -> Result<(), RegistryNameParseError> {
+    if ns.is_empty() {
+        return Err(RegistryNameParseError::EmptyNamespace(name.to_string()));
+    }
+    if key.is_empty() {
+        return Err(RegistryNameParseError::EmptyKey(name.to_string()));
+    }
+    let illegal = (ns.char_indices())
+        .chain(key.char_indices().map(|(pos, ch)| (pos + key_offset, ch)))
+        .find(|&(_, ch)| !is_valid_registry_name_char(ch));
+    match illegal {
+        Some((position, ch)) => Err(RegistryNameParseError::IllegalCharacter {
+            name: name.to_string(),
+            ch,
+            position,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks if the character is allowed in the namespace and key of a [`RegistryName`], `[a-z0-9_./-]`.
+pub const fn is_valid_registry_name_char(ch: char) -> bool {
+    matches!(ch, 'a'..='z' | '0'..='9' | '_' | '.' | '/' | '-')
+}
+
+/// Errors from parsing or validating a [`RegistryName`].
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum RegistryNameParseError {
+    /// The name has no `:` separating the namespace from the key.
+    #[error("Registry name {0:?} is missing the `:` namespace separator")]
+    MissingColon(String),
+    /// The part before the `:` is empty.
+    #[error("Registry name {0:?} has an empty namespace")]
+    EmptyNamespace(String),
+    /// The part after the `:` is empty.
+    #[error("Registry name {0:?} has an empty key")]
+    EmptyKey(String),
+    /// The name contains a character outside of `[a-z0-9_./-]`, apart from the single `:` separator.
+    #[error("Registry name {name:?} contains the illegal character {ch:?} at byte {position}, only [a-z0-9_./-] are allowed")]
+    IllegalCharacter {
+        /// The whole name.
+        name: String,
+        /// The first illegal character.
+        ch: char,
+        /// Byte position of the character in the name.
+        position: usize,
+    },
+}
+
+impl FromStr for RegistryName {
+    type Err = RegistryNameParseError;
+
+    /// Parses a `ns:key` name, or a plain `key` in the `gs` namespace.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.split_once(':') {
+            Some((ns, key)) => Self::from_parts(name, ns, key, ns.len() + 1),
+            None => Self::from_parts(name, GEOSIA_REGISTRY_DOMAIN, name, 0),
+        }
+    }
+}
+
+impl TryFrom<&str> for RegistryName {
+    type Error = RegistryNameParseError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl Serialize for RegistryName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            let mut fields = serializer.serialize_struct("RegistryName", 2)?;
+            fields.serialize_field("ns", &self.ns)?;
+            fields.serialize_field("key", &self.key)?;
+            fields.end()
+        }
+    }
+}
+
+/// The struct form of [`RegistryName`] for deserialization.
+#[derive(Deserialize)]
+#[serde(rename = "RegistryName")]
+struct RegistryNameFields {
+    ns: KString,
+    key: KString,
+}
+
+/// Either form of [`RegistryName`] for deserialization from self-describing formats.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RegistryNameRepr {
+    Compact(KString),
+    Fields(RegistryNameFields),
+}
+
+impl<'de> Deserialize<'de> for RegistryName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = if deserializer.is_human_readable() {
+            match RegistryNameRepr::deserialize(deserializer)? {
+                RegistryNameRepr::Compact(name) => return name.parse().map_err(serde::de::Error::custom),
+                RegistryNameRepr::Fields(fields) => fields,
+            }
+        } else {
+            RegistryNameFields::deserialize(deserializer)?
+        };
+        let name = RegistryName {
+            ns: fields.ns,
+            key: fields.key,
+        };
+        name.validate().map_err(serde::de::Error::custom)?;
+        Ok(name)
+    }
 }
 
 impl<'a> Equivalent<RegistryName> for RegistryNameRef<'a> {
@@ -111,6 +280,145 @@ impl TryFrom<u32> for RegistryId {
     }
 }
 
+// Safety: RegistryId is a transparent wrapper around NonZeroU32, for which these traits are implemented by bytemuck
+unsafe impl NoUninit for RegistryId {}
+unsafe impl ZeroableInOption for RegistryId {}
+unsafe impl PodInOption for RegistryId {}
+unsafe impl CheckedBitPattern for RegistryId {
+    type Bits = u32;
+
+    fn is_valid_bit_pattern(bits: &u32) -> bool {
+        *bits != 0
+    }
+}
+
+/// A [`RegistryId`] tagged with the type of the object it refers to, so that IDs from different registries can't be mixed up.
+///
+/// Has the same layout as a [`RegistryId`]. Because of the non-zero niche it is not [`Pod`](bytemuck::Pod) itself,
+/// but `Option<TypedRegistryId<Object>>` is, and the plain ID can be cast from bytes with [`bytemuck::checked`].
+#[repr(transparent)]
+pub struct TypedRegistryId<Object> {
+    id: RegistryId,
+    _object: PhantomData<fn() -> Object>,
+}
+
+impl<Object> TypedRegistryId<Object> {
+    /// Tags an untyped ID, the caller is responsible for making sure it comes from a registry of `Object`s.
+    #[inline]
+    pub const fn from_untyped(id: RegistryId) -> Self {
+        Self {
+            id,
+            _object: PhantomData,
+        }
+    }
+
+    /// Drops the type tag, for storing IDs of different registries together.
+    #[inline]
+    pub const fn untyped(self) -> RegistryId {
+        self.id
+    }
+
+    /// Converts raw ID bits (as stored in e.g. [`BlockId::registry_id_bits`](crate::voxeltypes::BlockId::registry_id_bits)), or `None` if the bits are zero.
+    #[inline]
+    pub const fn try_from_bits(bits: u32) -> Option<Self> {
+        match NonZeroU32::new(bits) {
+            Some(id) => Some(Self::from_untyped(RegistryId(id))),
+            None => None,
+        }
+    }
+
+    /// The raw ID bits, never zero.
+    #[inline]
+    pub const fn to_bits(self) -> u32 {
+        self.id.0.get()
+    }
+}
+
+// Manual trait implementations to avoid the bounds on `Object` derives would add.
+impl<Object> Copy for TypedRegistryId<Object> {}
+
+impl<Object> Clone for TypedRegistryId<Object> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Object> PartialEq for TypedRegistryId<Object> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<Object> Eq for TypedRegistryId<Object> {}
+
+impl<Object> PartialOrd for TypedRegistryId<Object> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Object> Ord for TypedRegistryId<Object> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<Object> Hash for TypedRegistryId<Object> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl<Object> std::fmt::Debug for TypedRegistryId<Object> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TypedRegistryId<{}>({})", std::any::type_name::<Object>(), self.id)
+    }
+}
+
+impl<Object> Display for TypedRegistryId<Object> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.id.fmt(f)
+    }
+}
+
+impl<Object> From<TypedRegistryId<Object>> for RegistryId {
+    fn from(value: TypedRegistryId<Object>) -> Self {
+        value.id
+    }
+}
+
+impl<Object> TryFrom<u32> for TypedRegistryId<Object> {
+    type Error = TryFromIntError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(Self::from_untyped(RegistryId::try_from(value)?))
+    }
+}
+
+impl<Object> Serialize for TypedRegistryId<Object> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize(serializer)
+    }
+}
+
+impl<'de, Object> Deserialize<'de> for TypedRegistryId<Object> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RegistryId::deserialize(deserializer).map(Self::from_untyped)
+    }
+}
+
+// Safety: TypedRegistryId is a transparent wrapper around RegistryId, the PhantomData is zero-sized
+unsafe impl<Object: 'static> NoUninit for TypedRegistryId<Object> {}
+unsafe impl<Object: 'static> ZeroableInOption for TypedRegistryId<Object> {}
+unsafe impl<Object: 'static> PodInOption for TypedRegistryId<Object> {}
+unsafe impl<Object: 'static> CheckedBitPattern for TypedRegistryId<Object> {
+    type Bits = u32;
+
+    fn is_valid_bit_pattern(bits: &u32) -> bool {
+        *bits != 0
+    }
+}
+
 impl Display for RegistryName {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.ns, self.key)
@@ -135,6 +443,14 @@ pub struct Registry<Object: RegistryObject> {
     next_free_id: NonZeroU32,
     id_to_obj: Vec<Option<Object>>,
     name_to_id: HashMap<RegistryName, RegistryId>,
+    #[serde(skip, default = "next_registry_generation")]
+    generation: u64,
+}
+
+/// Returns a generation number that was never returned before in this process.
+fn next_registry_generation() -> u64 {
+    static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl<Object: RegistryObject> Default for Registry<Object> {
@@ -143,6 +459,7 @@ impl<Object: RegistryObject> Default for Registry<Object> {
             next_free_id: NonZeroU32::new(1).unwrap(),
             id_to_obj: vec![None],
             name_to_id: HashMap::with_capacity(64),
+            generation: next_registry_generation(),
         }
     }
 }
@@ -164,28 +481,185 @@ pub enum RegistryError {
         /// The conflicting name.
         name: RegistryName,
     },
-    /// No more unallocated space in the registry. The allocator is a simple bump allocator, so if objects were removed, it might be possible to optimize the registry down to have free space again.
+    /// No more unallocated space in the registry. The allocator is a simple bump allocator, so if objects were removed, it might be possible to [`Registry::compact`] the registry down to have free space again.
     #[error("No free space in the registry")]
     NoFreeSpace,
 }
 
+/// A mapping from old to new registry IDs, produced when IDs get reassigned.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct RegistryIdRemap {
+    old_to_new: HashMap<RegistryId, RegistryId>,
+}
+
+impl RegistryIdRemap {
+    /// Records that the object at `old` is now at `new`.
+    pub fn insert(&mut self, old: RegistryId, new: RegistryId) {
+        self.old_to_new.insert(old, new);
+    }
+
+    /// Looks up the new ID of an object, or `None` if the old ID is not part of the mapping.
+    pub fn get(&self, old: RegistryId) -> Option<RegistryId> {
+        self.old_to_new.get(&old).copied()
+    }
+
+    /// Number of IDs in the mapping.
+    pub fn len(&self) -> usize {
+        self.old_to_new.len()
+    }
+
+    /// Checks if the mapping has no IDs.
+    pub fn is_empty(&self) -> bool {
+        self.old_to_new.is_empty()
+    }
+
+    /// Checks if every ID maps to itself, in which case the remap doesn't need to be applied.
+    pub fn is_identity(&self) -> bool {
+        self.old_to_new.iter().all(|(old, new)| old == new)
+    }
+
+    /// Iterates over all the `(old, new)` ID pairs, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (RegistryId, RegistryId)> + '_ {
+        self.old_to_new.iter().map(|(&old, &new)| (old, new))
+    }
+}
+
+/// A serializable copy of just the name to ID mapping of a [`Registry`], without the objects.
+/// Stored in saves and sent over the network to reconcile IDs with the registry of the current process.
+/// Deserialization goes through [`RegistrySnapshot::from_entries`], and additionally rejects entries not sorted by the ID.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawRegistrySnapshot")]
+pub struct RegistrySnapshot {
+    /// Pairs of IDs and names, sorted by the ID.
+    entries: Vec<(RegistryId, RegistryName)>,
+}
+
+/// The unvalidated serialized form of a [`RegistrySnapshot`].
+#[derive(Deserialize)]
+struct RawRegistrySnapshot {
+    entries: Vec<(RegistryId, RegistryName)>,
+}
+
+/// Possible errors when constructing a [`RegistrySnapshot`]
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum RegistrySnapshotError {
+    /// The same ID is mapped to more than one name.
+    #[error("Id {id} is present more than once in the snapshot")]
+    DuplicateId {
+        /// The repeated ID.
+        id: RegistryId,
+    },
+    /// The same name is mapped to more than one ID.
+    #[error("Name {name} is present more than once in the snapshot")]
+    DuplicateName {
+        /// The repeated name.
+        name: RegistryName,
+    },
+    /// The serialized entries are not sorted by the ID.
+    #[error("Id {id} is out of order in the snapshot")]
+    Unsorted {
+        /// The first ID that is smaller than the one preceding it.
+        id: RegistryId,
+    },
+}
+
+impl TryFrom<RawRegistrySnapshot> for RegistrySnapshot {
+    type Error = RegistrySnapshotError;
+
+    fn try_from(raw: RawRegistrySnapshot) -> Result<Self, Self::Error> {
+        if let Some(pair) = raw.entries.windows(2).find(|pair| pair[0].0 > pair[1].0) {
+            return Err(RegistrySnapshotError::Unsorted { id: pair[1].0 });
+        }
+        Self::from_entries(raw.entries)
+    }
+}
+
+/// The result of comparing a [`RegistrySnapshot`] against a live [`Registry`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct RegistrySnapshotDiff {
+    /// Mapping from the snapshot IDs to the live registry IDs, for all names present in both.
+    pub remap: RegistryIdRemap,
+    /// Names (with their snapshot IDs) that are present in the snapshot, but not in the live registry.
+    pub missing: Vec<(RegistryId, RegistryName)>,
+    /// Names (with their live IDs) that are present in the live registry, but not in the snapshot.
+    pub added: Vec<(RegistryId, RegistryName)>,
+}
+
+impl RegistrySnapshot {
+    /// Constructs a snapshot from the given ID-name pairs, in any order.
+    /// Fails if any ID or name is present more than once.
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = (RegistryId, RegistryName)>,
+    ) -> Result<Self, RegistrySnapshotError> {
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.sort_unstable();
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(RegistrySnapshotError::DuplicateId { id: pair[0].0 });
+        }
+        {
+            let mut names: HashMap<RegistryNameRef, ()> = HashMap::with_capacity(entries.len());
+            for (_, name) in entries.iter() {
+                if names.insert(name.as_ref(), ()).is_some() {
+                    return Err(RegistrySnapshotError::DuplicateName { name: name.clone() });
+                }
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Iterates over all the ID-name pairs, in ascending ID order.
+    pub fn iter(&self) -> impl Iterator<Item = (RegistryId, &RegistryName)> {
+        self.entries.iter().map(|(id, name)| (*id, name))
+    }
+
+    /// Number of names in the snapshot.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if the snapshot has no names.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compares the snapshot against a live registry, producing the ID remap from the snapshot to the registry and the lists of missing and added names.
+    pub fn diff<Object: RegistryObject>(&self, live: &Registry<Object>) -> RegistrySnapshotDiff {
+        let mut diff = RegistrySnapshotDiff::default();
+        let mut snapshot_names: HashMap<RegistryNameRef, ()> = HashMap::with_capacity(self.entries.len());
+        for (old_id, name) in self.entries.iter() {
+            snapshot_names.insert(name.as_ref(), ());
+            match live.lookup_name_to_id(name.as_ref()) {
+                Some(new_id) => diff.remap.insert(*old_id, new_id.untyped()),
+                None => diff.missing.push((*old_id, name.clone())),
+            }
+        }
+        diff.added = live
+            .iter()
+            .filter(|(_, name, _)| !snapshot_names.contains_key(&name.as_ref()))
+            .map(|(id, name, _)| (id.untyped(), name.clone()))
+            .collect();
+        diff
+    }
+}
+
 /// A registry of up to 2^32-2 named objects.
 impl<Object: RegistryObject> Registry<Object> {
     /// Low-level: Allocate the next free ID in the registry
-    pub fn allocate_id(&mut self) -> Result<RegistryId, RegistryError> {
+    pub fn allocate_id(&mut self) -> Result<TypedRegistryId<Object>, RegistryError> {
         let id = self.next_free_id;
         self.next_free_id = self.next_free_id.checked_add(1).ok_or(RegistryError::NoFreeSpace)?;
-        Ok(RegistryId(id))
+        Ok(TypedRegistryId::from_untyped(RegistryId(id)))
     }
 
     /// Try to put the object in the registry, allocating it a new ID.
     /// On failure, no ID is allocated and a precise error is returned.
-    pub fn push_object(&mut self, object: Object) -> Result<RegistryId, RegistryError> {
+    pub fn push_object(&mut self, object: Object) -> Result<TypedRegistryId<Object>, RegistryError> {
         let name = object.registry_name().to_owned();
         if self.name_to_id.contains_key(&name) {
             return Err(RegistryError::NameAlreadyExists { name });
         }
-        let id = self.allocate_id()?;
+        let typed_id = self.allocate_id()?;
+        let id = typed_id.untyped();
         let raw_id = id.0.get() as usize;
         if self.id_to_obj.len() <= raw_id {
             self.id_to_obj.resize_with(raw_id + 32, || None);
@@ -198,11 +672,13 @@ impl<Object: RegistryObject> Registry<Object> {
         }
         self.id_to_obj[raw_id] = Some(object);
         self.name_to_id.insert(name, id);
-        Ok(id)
+        self.generation = next_registry_generation();
+        Ok(typed_id)
     }
 
     /// Low-level: Attempt to insert an object-id pair into the registry directly, useful for deserialization or manually tweaking registry contents.
-    pub fn insert_object_with_id(&mut self, id: RegistryId, object: Object) -> Result<(), RegistryError> {
+    pub fn insert_object_with_id(&mut self, id: TypedRegistryId<Object>, object: Object) -> Result<(), RegistryError> {
+        let id = id.untyped();
         let raw_id = id.0.get() as usize;
         if id.0 == NonZeroU32::MAX {
             return Err(RegistryError::NoFreeSpace);
@@ -224,19 +700,235 @@ impl<Object: RegistryObject> Registry<Object> {
         }
         self.id_to_obj[raw_id] = Some(object);
         self.name_to_id.insert(name, id);
+        self.generation = next_registry_generation();
         Ok(())
     }
 
     /// Given a namespaced name, look up an object and its ID, or return `None` if it's not found.
-    pub fn lookup_name_to_object(&self, name: RegistryNameRef) -> Option<(RegistryId, &Object)> {
+    pub fn lookup_name_to_object(&self, name: RegistryNameRef) -> Option<(TypedRegistryId<Object>, &Object)> {
         let id = *self.name_to_id.get(&name)?;
         let obj = self.id_to_obj.get(id.0.get() as usize)?.as_ref()?;
-        Some((id, obj))
+        Some((TypedRegistryId::from_untyped(id), obj))
+    }
+
+    /// Given a registry object ID, look up an object, or return `None` if it's not found.
+    pub fn lookup_id_to_object(&self, id: TypedRegistryId<Object>) -> Option<&Object> {
+        self.id_to_obj.get(id.to_bits() as usize)?.as_ref()
+    }
+
+    /// Given a namespaced name, look up a mutable reference to an object and its ID, or return `None` if it's not found.
+    ///
+    /// The object's registry name must not be changed through the reference.
+    pub fn lookup_name_to_object_mut(
+        &mut self,
+        name: RegistryNameRef,
+    ) -> Option<(TypedRegistryId<Object>, &mut Object)> {
+        let id = *self.name_to_id.get(&name)?;
+        let obj = self.id_to_obj.get_mut(id.0.get() as usize)?.as_mut()?;
+        Some((TypedRegistryId::from_untyped(id), obj))
+    }
+
+    /// Given a registry object ID, look up a mutable reference to an object, or return `None` if it's not found.
+    ///
+    /// The object's registry name must not be changed through the reference.
+    pub fn lookup_id_to_object_mut(&mut self, id: TypedRegistryId<Object>) -> Option<&mut Object> {
+        self.id_to_obj.get_mut(id.to_bits() as usize)?.as_mut()
+    }
+
+    /// Given a namespaced name, look up its ID, or return `None` if it's not found.
+    pub fn lookup_name_to_id(&self, name: RegistryNameRef) -> Option<TypedRegistryId<Object>> {
+        self.name_to_id.get(&name).copied().map(TypedRegistryId::from_untyped)
+    }
+
+    /// Number of objects in the registry.
+    pub fn len(&self) -> usize {
+        self.name_to_id.len()
+    }
+
+    /// A number that changes whenever an object is added, removed or moved to a different ID, unique across all registries in the process.
+    /// Used to detect when data derived from the registry, like [tags](crate::registry_tags::RegistryTags), needs to be rebuilt.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// One past the highest ID that can currently be in use, useful for sizing tables indexed by the raw ID.
+    pub fn id_upper_bound(&self) -> u32 {
+        self.next_free_id.get()
+    }
+
+    /// Checks if the registry has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.name_to_id.is_empty()
+    }
+
+    /// Iterates over all the objects in the registry, in ascending ID order.
+    pub fn iter(&self) -> impl Iterator<Item = (TypedRegistryId<Object>, &RegistryName, &Object)> {
+        self.id_to_obj.iter().enumerate().filter_map(|(raw_id, obj)| {
+            let obj = obj.as_ref()?;
+            let (name, &id) = self.name_to_id.get_key_value(&obj.registry_name())?;
+            debug_assert_eq!(id.0.get() as usize, raw_id);
+            Some((TypedRegistryId::from_untyped(id), name, obj))
+        })
+    }
+
+    /// Iterates over mutable references to all the objects in the registry, in ascending ID order.
+    ///
+    /// The objects' registry names must not be changed through the references.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (TypedRegistryId<Object>, &mut Object)> {
+        self.id_to_obj.iter_mut().enumerate().filter_map(|(raw_id, obj)| {
+            let obj = obj.as_mut()?;
+            let id = TypedRegistryId::try_from_bits(raw_id as u32)?;
+            Some((id, obj))
+        })
+    }
+
+    /// Removes the object with the given ID from the registry, returning it if it was present.
+    /// The ID is not reused by future insertions until the registry is [compacted](Self::compact).
+    pub fn remove_object(&mut self, id: TypedRegistryId<Object>) -> Option<Object> {
+        let obj = self.id_to_obj.get_mut(id.to_bits() as usize)?.take()?;
+        self.name_to_id.remove(&obj.registry_name());
+        self.generation = next_registry_generation();
+        Some(obj)
+    }
+
+    /// Removes the object with the given name from the registry, returning it and its old ID if it was present.
+    pub fn remove_object_by_name(&mut self, name: RegistryNameRef) -> Option<(TypedRegistryId<Object>, Object)> {
+        let id = self.lookup_name_to_id(name)?;
+        self.remove_object(id).map(|obj| (id, obj))
+    }
+
+    /// Converts the registry into an immutable, compact form optimized for concurrent lookups, see [`FrozenRegistry`].
+    pub fn freeze(self) -> FrozenRegistry<Object> {
+        let mut name_to_id = self.name_to_id;
+        let mut entries: Vec<Option<(RegistryName, Object)>> = self
+            .id_to_obj
+            .into_iter()
+            .map(|obj| {
+                let obj = obj?;
+                let (name, _) = name_to_id
+                    .remove_entry(&obj.registry_name())
+                    .expect("Registry object missing from the name map");
+                Some((name, obj))
+            })
+            .collect();
+        while matches!(entries.last(), Some(None)) {
+            entries.pop();
+        }
+        let mut sorted_ids: Vec<TypedRegistryId<Object>> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_some())
+            .map(|(raw_id, _)| TypedRegistryId::try_from_bits(raw_id as u32).expect("Object stored at ID 0"))
+            .collect();
+        sorted_ids.sort_unstable_by(|&a, &b| {
+            let name = |id: TypedRegistryId<Object>| &entries[id.to_bits() as usize].as_ref().unwrap().0;
+            name(a).cmp(name(b))
+        });
+        FrozenRegistry {
+            entries: entries.into_boxed_slice(),
+            sorted_ids: sorted_ids.into_boxed_slice(),
+        }
+    }
+
+    /// Creates a copy of the name to ID mapping of this registry, see [`RegistrySnapshot`].
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot {
+            entries: self.iter().map(|(id, name, _)| (id.untyped(), name.clone())).collect(),
+        }
+    }
+
+    /// Reassigns the IDs of all objects to be dense (starting at 1, preserving their relative order), freeing up the IDs of removed objects.
+    ///
+    /// Returns the mapping of old to new IDs for every object in the registry, which must be applied to all stored IDs.
+    pub fn compact(&mut self) -> RegistryIdRemap {
+        let mut remap = RegistryIdRemap::default();
+        let old_objects = std::mem::take(&mut self.id_to_obj);
+        self.id_to_obj = Vec::with_capacity(self.name_to_id.len() + 1);
+        self.id_to_obj.push(None);
+        for (old_raw_id, obj) in old_objects.into_iter().enumerate() {
+            let Some(obj) = obj else { continue };
+            let old_id = RegistryId(NonZeroU32::new(old_raw_id as u32).expect("Object stored at ID 0"));
+            let new_id = RegistryId(NonZeroU32::new(self.id_to_obj.len() as u32).unwrap());
+            *self
+                .name_to_id
+                .get_mut(&obj.registry_name())
+                .expect("Registry object missing from the name map") = new_id;
+            self.id_to_obj.push(Some(obj));
+            remap.insert(old_id, new_id);
+        }
+        self.next_free_id = NonZeroU32::new(self.id_to_obj.len() as u32).unwrap();
+        if !remap.is_identity() {
+            self.generation = next_registry_generation();
+        }
+        remap
+    }
+}
+
+/// An immutable registry produced by [`Registry::freeze`] once all the objects are registered.
+///
+/// Objects are stored contiguously indexed by their ID, and names are looked up by binary search in an ID array sorted by name,
+/// so there is no hashing or locking involved in lookups. Wrap it in an [`std::sync::Arc`] to share it between threads.
+pub struct FrozenRegistry<Object: RegistryObject> {
+    /// Indexed by the raw ID, `None` for unused IDs.
+    entries: Box<[Option<(RegistryName, Object)>]>,
+    /// All the used IDs, sorted by the name of their object.
+    sorted_ids: Box<[TypedRegistryId<Object>]>,
+}
+
+impl<Object: RegistryObject> FrozenRegistry<Object> {
+    fn entry(&self, id: TypedRegistryId<Object>) -> Option<&(RegistryName, Object)> {
+        self.entries.get(id.to_bits() as usize)?.as_ref()
+    }
+
+    /// Given a namespaced name, look up its ID, or return `None` if it's not found.
+    pub fn lookup_name_to_id(&self, name: RegistryNameRef) -> Option<TypedRegistryId<Object>> {
+        let idx = self
+            .sorted_ids
+            .binary_search_by(|&id| self.entry(id).unwrap().0.as_ref().cmp(&name))
+            .ok()?;
+        Some(self.sorted_ids[idx])
+    }
+
+    /// Given a namespaced name, look up an object and its ID, or return `None` if it's not found.
+    pub fn lookup_name_to_object(&self, name: RegistryNameRef) -> Option<(TypedRegistryId<Object>, &Object)> {
+        let id = self.lookup_name_to_id(name)?;
+        Some((id, &self.entry(id)?.1))
     }
 
     /// Given a registry object ID, look up an object, or return `None` if it's not found.
-    pub fn lookup_id_to_object(&self, id: RegistryId) -> Option<&Object> {
-        self.id_to_obj.get(id.0.get() as usize)?.as_ref()
+    #[inline]
+    pub fn lookup_id_to_object(&self, id: TypedRegistryId<Object>) -> Option<&Object> {
+        Some(&self.entry(id)?.1)
+    }
+
+    /// Given a registry object ID, look up the object's name, or return `None` if it's not found.
+    pub fn lookup_id_to_name(&self, id: TypedRegistryId<Object>) -> Option<&RegistryName> {
+        Some(&self.entry(id)?.0)
+    }
+
+    /// Number of objects in the registry.
+    pub fn len(&self) -> usize {
+        self.sorted_ids.len()
+    }
+
+    /// Checks if the registry has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.sorted_ids.is_empty()
+    }
+
+    /// Iterates over all the objects in the registry, in ascending ID order.
+    pub fn iter(&self) -> impl Iterator<Item = (TypedRegistryId<Object>, &RegistryName, &Object)> {
+        self.entries.iter().enumerate().filter_map(|(raw_id, entry)| {
+            let (name, obj) = entry.as_ref()?;
+            Some((TypedRegistryId::try_from_bits(raw_id as u32)?, name, obj))
+        })
+    }
+
+    /// Creates a copy of the name to ID mapping of this registry, see [`RegistrySnapshot`].
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot {
+            entries: self.iter().map(|(id, name, _)| (id.untyped(), name.clone())).collect(),
+        }
     }
 }
 
@@ -257,9 +949,9 @@ mod test {
     pub fn simple_registry() {
         let mut reg: Registry<DummyObject> = Registry::default();
         let a_id = reg.push_object(DummyObject(RegistryName::geosia("a"))).unwrap();
-        assert_eq!(a_id.0.get(), 1);
-        let b_id = RegistryId::try_from(2).unwrap();
-        let c_id = RegistryId::try_from(3).unwrap(); // non-existent
+        assert_eq!(a_id.to_bits(), 1);
+        let b_id = TypedRegistryId::try_from(2).unwrap();
+        let c_id = TypedRegistryId::try_from(3).unwrap(); // non-existent
         reg.insert_object_with_id(b_id, DummyObject(RegistryName::geosia("b")))
             .unwrap();
         assert!(reg.push_object(DummyObject(RegistryName::geosia("a"))).is_err());
@@ -295,4 +987,304 @@ mod test {
             None
         );
     }
+
+    #[test]
+    pub fn registry_name_parse() {
+        let stone: RegistryName = "gs:stone".parse().unwrap();
+        assert_eq!(stone, RegistryName::geosia("stone"));
+        assert_eq!(RegistryName::try_from("stone"), Ok(stone.clone()));
+        assert_eq!(RegistryName::parse_qualified("gs:stone"), Ok(stone.clone()));
+        assert_eq!(stone.to_string().parse::<RegistryName>(), Ok(stone));
+        let modded = RegistryName::try_from("my-mod:blocks/oak_log.v2").unwrap();
+        assert_eq!(modded.ns.as_str(), "my-mod");
+        assert_eq!(modded.key.as_str(), "blocks/oak_log.v2");
+        assert_eq!(modded.validate(), Ok(()));
+
+        assert_eq!(
+            RegistryName::parse_qualified("stone"),
+            Err(RegistryNameParseError::MissingColon(String::from("stone")))
+        );
+        assert_eq!(
+            RegistryName::try_from(":stone"),
+            Err(RegistryNameParseError::EmptyNamespace(String::from(":stone")))
+        );
+        assert_eq!(
+            RegistryName::try_from("gs:"),
+            Err(RegistryNameParseError::EmptyKey(String::from("gs:")))
+        );
+        assert_eq!(
+            RegistryName::try_from(""),
+            Err(RegistryNameParseError::EmptyKey(String::new()))
+        );
+        let illegal = |name: &str, ch: char, position: usize| RegistryNameParseError::IllegalCharacter {
+            name: String::from(name),
+            ch,
+            position,
+        };
+        assert_eq!(RegistryName::try_from("Stone"), Err(illegal("Stone", 'S', 0)));
+        assert_eq!(RegistryName::try_from("gs:a:b"), Err(illegal("gs:a:b", ':', 4)));
+        assert_eq!(
+            RegistryName::try_from("gs:big stone"),
+            Err(illegal("gs:big stone", ' ', 6))
+        );
+        assert_eq!(RegistryName::try_from("gś:stone"), Err(illegal("gś:stone", 'ś', 1)));
+        assert_eq!(
+            RegistryName {
+                ns: GEOSIA_REGISTRY_DOMAIN_KS.clone(),
+                key: KString::from_static("UPPER"),
+            }
+            .validate(),
+            Err(illegal("gs:UPPER", 'U', 3))
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Invalid registry name")]
+    pub fn registry_name_geosia_invalid() {
+        let _ = RegistryName::geosia("big stone");
+    }
+
+    #[test]
+    pub fn registry_name_serde() {
+        use serde_test::{Compact, Configure, Readable, Token};
+
+        let name = RegistryName {
+            ns: KString::from_static("mod"),
+            key: KString::from_static("ore"),
+        };
+        serde_test::assert_tokens(&name.clone().readable(), &[Token::Str("mod:ore")]);
+        let struct_tokens = [
+            Token::Struct {
+                name: "RegistryName",
+                len: 2,
+            },
+            Token::Str("ns"),
+            Token::Str("mod"),
+            Token::Str("key"),
+            Token::Str("ore"),
+            Token::StructEnd,
+        ];
+        serde_test::assert_tokens(&name.clone().compact(), &struct_tokens);
+        serde_test::assert_de_tokens(&name.clone().readable(), &struct_tokens);
+        serde_test::assert_de_tokens(&RegistryName::geosia("ore").readable(), &[Token::Str("ore")]);
+
+        serde_test::assert_de_tokens_error::<Readable<RegistryName>>(
+            &[Token::Str("mod:Ore")],
+            &RegistryName::try_from("mod:Ore").unwrap_err().to_string(),
+        );
+        serde_test::assert_de_tokens_error::<Compact<RegistryName>>(
+            &[
+                Token::Struct {
+                    name: "RegistryName",
+                    len: 2,
+                },
+                Token::Str("ns"),
+                Token::Str(""),
+                Token::Str("key"),
+                Token::Str("ore"),
+                Token::StructEnd,
+            ],
+            &RegistryNameParseError::EmptyNamespace(String::from(":ore")).to_string(),
+        );
+    }
+
+    #[test]
+    pub fn registry_iter_remove_compact() {
+        let mut reg: Registry<DummyObject> = Registry::default();
+        let ids: Vec<TypedRegistryId<DummyObject>> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|key| reg.push_object(DummyObject(RegistryName::geosia(key))).unwrap())
+            .collect();
+        assert_eq!(reg.len(), 4);
+        assert_eq!(
+            reg.iter()
+                .map(|(id, name, _)| (id, name.key.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(ids[0], "a"), (ids[1], "b"), (ids[2], "c"), (ids[3], "d")]
+        );
+
+        let b = reg.remove_object(ids[1]).unwrap();
+        assert_eq!(b.0.key.as_str(), "b");
+        assert!(reg.remove_object(ids[1]).is_none());
+        let dyn_c = KString::from_string(String::from("c"));
+        assert_eq!(
+            reg.remove_object_by_name(RegistryNameRef::geosia(&dyn_c))
+                .map(|(id, _)| id),
+            Some(ids[2])
+        );
+        assert_eq!(reg.len(), 2);
+        assert!(reg.lookup_id_to_object(ids[1]).is_none());
+        // Removed IDs are not reused before compaction
+        let e_id = reg.push_object(DummyObject(RegistryName::geosia("e"))).unwrap();
+        assert_eq!(e_id.to_bits(), 5);
+
+        let obj = reg.lookup_id_to_object_mut(ids[0]).unwrap();
+        assert_eq!(obj.0.key.as_str(), "a");
+        // Renaming breaks the name index, so it's reverted before the next name lookup
+        obj.0 = RegistryName::geosia("a2");
+        assert_eq!(reg.lookup_id_to_object(ids[0]).unwrap().0.key.as_str(), "a2");
+        reg.lookup_id_to_object_mut(ids[0]).unwrap().0 = RegistryName::geosia("a");
+        assert_eq!(reg.iter_mut().count(), 3);
+
+        let remap = reg.compact();
+        assert_eq!(remap.len(), 3);
+        assert!(!remap.is_identity());
+        assert_eq!(remap.get(ids[0].untyped()), Some(ids[0].untyped()));
+        assert_eq!(remap.get(ids[1].untyped()), None);
+        assert_eq!(remap.get(ids[3].untyped()).map(|id| id.0.get()), Some(2));
+        assert_eq!(remap.get(e_id.untyped()).map(|id| id.0.get()), Some(3));
+        assert_eq!(
+            reg.iter()
+                .map(|(id, name, _)| (id.to_bits(), name.key.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "a"), (2, "d"), (3, "e")]
+        );
+        let dyn_d = KString::from_string(String::from("d"));
+        assert_eq!(
+            reg.lookup_name_to_id(RegistryNameRef::geosia(&dyn_d))
+                .map(|id| id.to_bits()),
+            Some(2)
+        );
+        let f_id = reg.push_object(DummyObject(RegistryName::geosia("f"))).unwrap();
+        assert_eq!(f_id.to_bits(), 4);
+        assert!(reg.compact().is_identity());
+    }
+
+    #[test]
+    pub fn registry_snapshot_diff() {
+        let mut saved: Registry<DummyObject> = Registry::default();
+        for key in ["a", "b", "c"] {
+            saved.push_object(DummyObject(RegistryName::geosia(key))).unwrap();
+        }
+        let snapshot = saved.snapshot();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(
+            RegistrySnapshot::from_entries(snapshot.entries.iter().rev().cloned()),
+            Ok(snapshot.clone())
+        );
+
+        let mut live: Registry<DummyObject> = Registry::default();
+        for key in ["d", "c", "a"] {
+            live.push_object(DummyObject(RegistryName::geosia(key))).unwrap();
+        }
+        let id = |v: u32| RegistryId::try_from(v).unwrap();
+        let diff = snapshot.diff(&live);
+        assert_eq!(diff.remap.len(), 2);
+        assert_eq!(diff.remap.get(id(1)), Some(id(3)));
+        assert_eq!(diff.remap.get(id(2)), None);
+        assert_eq!(diff.remap.get(id(3)), Some(id(2)));
+        assert_eq!(diff.missing, vec![(id(2), RegistryName::geosia("b"))]);
+        assert_eq!(diff.added, vec![(id(1), RegistryName::geosia("d"))]);
+
+        let self_diff = live.snapshot().diff(&live);
+        assert!(self_diff.remap.is_identity());
+        assert!(self_diff.missing.is_empty() && self_diff.added.is_empty());
+    }
+
+    #[test]
+    pub fn registry_snapshot_validation() {
+        let id = |v: u32| RegistryId::try_from(v).unwrap();
+        assert_eq!(
+            RegistrySnapshot::from_entries([(id(1), RegistryName::geosia("a")), (id(1), RegistryName::geosia("b"))]),
+            Err(RegistrySnapshotError::DuplicateId { id: id(1) })
+        );
+        assert_eq!(
+            RegistrySnapshot::from_entries([(id(1), RegistryName::geosia("a")), (id(2), RegistryName::geosia("a"))]),
+            Err(RegistrySnapshotError::DuplicateName {
+                name: RegistryName::geosia("a")
+            })
+        );
+
+        let snapshot: RegistrySnapshot = ron::from_str(r#"(entries: [((1), "gs:a"), ((3), "gs:b")])"#).unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(ron::from_str(&ron::to_string(&snapshot).unwrap()), Ok(snapshot));
+        for invalid in [
+            r#"(entries: [((3), "gs:b"), ((1), "gs:a")])"#,
+            r#"(entries: [((1), "gs:a"), ((1), "gs:b")])"#,
+            r#"(entries: [((1), "gs:a"), ((2), "gs:a")])"#,
+        ] {
+            assert!(ron::from_str::<RegistrySnapshot>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    pub fn frozen_registry() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FrozenRegistry<DummyObject>>();
+
+        let mut reg: Registry<DummyObject> = Registry::default();
+        for key in ["zeta", "alpha", "mu", "beta"] {
+            reg.push_object(DummyObject(RegistryName::geosia(key))).unwrap();
+        }
+        let id = |v: u32| TypedRegistryId::try_from(v).unwrap();
+        reg.insert_object_with_id(
+            id(10),
+            DummyObject(RegistryName {
+                ns: KString::from_static("mod"),
+                key: KString::from_static("alpha"),
+            }),
+        )
+        .unwrap();
+        reg.remove_object(id(3));
+        let snapshot = reg.snapshot();
+        let expected: Vec<(TypedRegistryId<DummyObject>, RegistryName)> =
+            reg.iter().map(|(id, name, _)| (id, name.clone())).collect();
+
+        let frozen = std::sync::Arc::new(reg.freeze());
+        assert_eq!(frozen.len(), 4);
+        assert_eq!(frozen.snapshot(), snapshot);
+        assert_eq!(
+            frozen
+                .iter()
+                .map(|(id, name, _)| (id, name.clone()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        for (id, name) in expected.iter() {
+            assert_eq!(frozen.lookup_name_to_id(name.as_ref()), Some(*id));
+            assert_eq!(frozen.lookup_id_to_object(*id).map(|o| &o.0), Some(name));
+            assert_eq!(frozen.lookup_id_to_name(*id), Some(name));
+            assert_eq!(frozen.lookup_name_to_object(name.as_ref()).map(|(i, _)| i), Some(*id));
+        }
+        let dyn_mu = KString::from_string(String::from("mu"));
+        let dyn_nope = KString::from_string(String::from("nope"));
+        assert_eq!(frozen.lookup_name_to_id(RegistryNameRef::geosia(&dyn_mu)), None);
+        assert_eq!(frozen.lookup_name_to_id(RegistryNameRef::geosia(&dyn_nope)), None);
+        assert!(frozen.lookup_id_to_object(id(3)).is_none());
+        assert!(frozen.lookup_id_to_object(id(100)).is_none());
+
+        let shared = std::sync::Arc::clone(&frozen);
+        let from_thread = std::thread::spawn(move || shared.lookup_id_to_name(id(10)).cloned())
+            .join()
+            .unwrap();
+        assert_eq!(from_thread.map(|n| n.to_string()), Some(String::from("mod:alpha")));
+    }
+
+    #[test]
+    pub fn typed_registry_id() {
+        use std::mem::size_of;
+
+        use serde_test::Token;
+
+        assert_eq!(size_of::<TypedRegistryId<DummyObject>>(), size_of::<u32>());
+        assert_eq!(size_of::<Option<TypedRegistryId<DummyObject>>>(), size_of::<u32>());
+
+        let mut reg: Registry<DummyObject> = Registry::default();
+        let a_id = reg.push_object(DummyObject(RegistryName::geosia("a"))).unwrap();
+        assert_eq!(TypedRegistryId::try_from_bits(a_id.to_bits()), Some(a_id));
+        assert_eq!(TypedRegistryId::<DummyObject>::try_from_bits(0), None);
+        assert_eq!(TypedRegistryId::from_untyped(a_id.untyped()), a_id);
+        assert_eq!(RegistryId::from(a_id), a_id.untyped());
+
+        let ids: [Option<TypedRegistryId<DummyObject>>; 2] = [Some(a_id), None];
+        assert_eq!(bytemuck::cast::<_, [u32; 2]>(ids), [1, 0]);
+        assert_eq!(
+            bytemuck::checked::try_cast::<u32, TypedRegistryId<DummyObject>>(1),
+            Ok(a_id)
+        );
+        assert!(bytemuck::checked::try_cast::<u32, TypedRegistryId<DummyObject>>(0).is_err());
+
+        serde_test::assert_tokens(&a_id, &[Token::NewtypeStruct { name: "RegistryId" }, Token::U32(1)]);
+    }
 }
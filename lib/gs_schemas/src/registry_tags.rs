@@ -0,0 +1,650 @@
+//! Named groups of registry objects ("tags"), defined by name in data files and resolved into fast bitsets against a [`Registry`].
+//!
+//! Tag files are RON files laid out as `<root>/<namespace>/<key>.ron`, so `tags/blocks/gs/logs.ron` defines `gs:logs` when loaded from `tags/blocks`:
+//! ```ron
+//! (
+//!     values: ["gs:oak_log", "gs:birch_log", "#gs:modded_logs"],
+//! )
+//! ```
+//! Entries starting with `#` include all the objects of another tag.
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use bitvec::prelude::*;
+use hashbrown::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::registry::{
+    Registry, RegistryName, RegistryNameParseError, RegistryNameRef, RegistryObject, TypedRegistryId,
+};
+
+/// One entry of a tag definition: either a single object, or all the objects of another tag.
+///
+/// Serialized as the object name, or the tag name prefixed with `#`.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub enum TagEntry {
+    /// A single registry object.
+    Object(RegistryName),
+    /// All the objects of another tag.
+    Tag(RegistryName),
+}
+
+impl Display for TagEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagEntry::Object(name) => write!(f, "{name}"),
+            TagEntry::Tag(name) => write!(f, "#{name}"),
+        }
+    }
+}
+
+impl FromStr for TagEntry {
+    type Err = RegistryNameParseError;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        match entry.strip_prefix('#') {
+            Some(tag) => Ok(TagEntry::Tag(tag.parse()?)),
+            None => Ok(TagEntry::Object(entry.parse()?)),
+        }
+    }
+}
+
+impl Serialize for TagEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TagEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entry = String::deserialize(deserializer)?;
+        entry.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The contents of a single tag data file.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct TagDefinition {
+    /// If set, this definition replaces the entries of previously loaded definitions of the same tag instead of extending them.
+    #[serde(default)]
+    pub replace: bool,
+    /// The objects and other tags included in this tag.
+    pub values: Vec<TagEntry>,
+}
+
+/// Possible errors from loading tag data files.
+#[derive(Debug, Error)]
+pub enum TagLoadError {
+    /// The file or directory could not be read.
+    #[error("Could not read tag data at {}: {source}", path.display())]
+    Io {
+        /// The path that failed to be read.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The path of the file doesn't form a valid tag name.
+    #[error("Tag file {} doesn't have a valid tag name: {source}", path.display())]
+    InvalidName {
+        /// The path of the tag file.
+        path: PathBuf,
+        /// The name validation error.
+        source: RegistryNameParseError,
+    },
+    /// The file is not a valid tag definition, the error includes the line and column.
+    #[error("Could not parse tag file {}:{source}", path.display())]
+    Parse {
+        /// The path of the tag file.
+        path: PathBuf,
+        /// The parsing error.
+        source: ron::error::SpannedError,
+    },
+}
+
+/// Possible errors from resolving tag definitions against a registry.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum TagBuildError {
+    /// A tag refers to an object name that is not in the registry.
+    #[error("Tag {tag} refers to {object}, which is not registered")]
+    UnknownObject {
+        /// The tag with the invalid entry.
+        tag: RegistryName,
+        /// The unregistered object name.
+        object: RegistryName,
+    },
+    /// A tag includes a tag that is not defined.
+    #[error("Tag {tag} includes #{included}, which is not defined")]
+    UnknownTag {
+        /// The tag with the invalid entry.
+        tag: RegistryName,
+        /// The undefined tag name.
+        included: RegistryName,
+    },
+    /// Tags include each other in a cycle, listed in inclusion order starting and ending with the same tag.
+    #[error("Tags include each other in a cycle: {}", .0.iter().map(|t| format!("#{t}")).collect::<Vec<_>>().join(" -> "))]
+    Cycle(Vec<RegistryName>),
+}
+
+/// Name-based tag definitions, independent of the numeric IDs of any registry.
+/// [Build](Self::build) them into [`RegistryTags`] for fast membership checks.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct TagDefinitions {
+    tags: HashMap<RegistryName, Vec<TagEntry>>,
+}
+
+impl TagDefinitions {
+    /// Adds a tag definition, extending or replacing (if [`TagDefinition::replace`] is set) the existing entries of the tag.
+    pub fn add(&mut self, tag: RegistryName, definition: TagDefinition) {
+        let entries = self.tags.entry(tag).or_default();
+        if definition.replace {
+            entries.clear();
+        }
+        for entry in definition.values {
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    /// Adds a single entry to a tag, creating the tag if needed.
+    pub fn add_entry(&mut self, tag: RegistryName, entry: TagEntry) {
+        self.add(
+            tag,
+            TagDefinition {
+                replace: false,
+                values: vec![entry],
+            },
+        );
+    }
+
+    /// Looks up the entries of a tag, or `None` if it's not defined.
+    pub fn get(&self, tag: RegistryNameRef) -> Option<&[TagEntry]> {
+        self.tags.get(&tag).map(Vec::as_slice)
+    }
+
+    /// Number of defined tags.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Checks if no tags are defined.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Iterates over all the tag names and their entries, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&RegistryName, &[TagEntry])> {
+        self.tags.iter().map(|(name, entries)| (name, entries.as_slice()))
+    }
+
+    /// Parses the RON source of a tag data file and [adds](Self::add) it as the given tag.
+    pub fn add_ron_str(&mut self, tag: RegistryName, source: &str) -> Result<(), ron::error::SpannedError> {
+        let definition: TagDefinition = ron::from_str(source)?;
+        self.add(tag, definition);
+        Ok(())
+    }
+
+    /// Loads all the `<namespace>/<key>.ron` tag files under the given directory, returning the number of files loaded.
+    /// Keys can contain `/`, taken from nested directories. Files are loaded in sorted path order, so that [`TagDefinition::replace`] is deterministic.
+    /// All the files are parsed before any definitions are added, so on failure the existing definitions are left untouched.
+    pub fn load_dir(&mut self, root: &Path) -> Result<usize, TagLoadError> {
+        let mut files = Vec::new();
+        collect_ron_files(root, &mut files).map_err(|(path, source)| TagLoadError::Io { path, source })?;
+        files.sort();
+        let mut definitions = Vec::with_capacity(files.len());
+        for path in files.iter() {
+            let io_error = |source| TagLoadError::Io {
+                path: path.clone(),
+                source,
+            };
+            let relative = path
+                .strip_prefix(root)
+                .expect("Collected file outside of the root")
+                .with_extension("");
+            let mut components = relative.components().map(|c| c.as_os_str().to_string_lossy());
+            let ns = components.next().unwrap_or_default();
+            let key = components.collect::<Vec<_>>().join("/");
+            let tag =
+                RegistryName::parse_qualified(&format!("{ns}:{key}")).map_err(|source| TagLoadError::InvalidName {
+                    path: path.clone(),
+                    source,
+                })?;
+            let source = std::fs::read_to_string(path).map_err(io_error)?;
+            let definition: TagDefinition = ron::from_str(&source).map_err(|source| TagLoadError::Parse {
+                path: path.clone(),
+                source,
+            })?;
+            definitions.push((tag, definition));
+        }
+        for (tag, definition) in definitions {
+            self.add(tag, definition);
+        }
+        Ok(files.len())
+    }
+
+    /// Resolves the names in all the tags against the registry, including the contents of nested tags.
+    pub fn build<Object: RegistryObject>(
+        &self,
+        registry: &Registry<Object>,
+    ) -> Result<RegistryTags<Object>, TagBuildError> {
+        let mut resolver = TagResolver {
+            definitions: self,
+            registry,
+            resolved: HashMap::with_capacity(self.tags.len()),
+            stack: Vec::new(),
+        };
+        for tag in self.tags.keys() {
+            resolver.resolve(tag)?;
+        }
+        let tags = resolver
+            .resolved
+            .into_iter()
+            .map(|(name, bits)| (name.clone(), Tag::from_bits(bits)))
+            .collect();
+        Ok(RegistryTags {
+            tags,
+            generation: registry.generation(),
+        })
+    }
+
+    /// Rebuilds the tags if the registry changed since they were built, returning whether a rebuild happened.
+    /// On failure, the old tags are left untouched.
+    pub fn rebuild_if_stale<Object: RegistryObject>(
+        &self,
+        tags: &mut RegistryTags<Object>,
+        registry: &Registry<Object>,
+    ) -> Result<bool, TagBuildError> {
+        if !tags.is_stale(registry) {
+            return Ok(false);
+        }
+        *tags = self.build(registry)?;
+        Ok(true)
+    }
+}
+
+/// Recursively collects the paths of all the `.ron` files in the directory, in no particular order.
+/// On failure, returns the path that could not be read along with the error.
+pub(crate) fn collect_ron_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), (PathBuf, std::io::Error)> {
+    let io_error = |source| (dir.to_owned(), source);
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.is_dir() {
+            collect_ron_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "ron") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Depth-first resolution of tags with memoization and cycle detection.
+struct TagResolver<'a, Object: RegistryObject> {
+    definitions: &'a TagDefinitions,
+    registry: &'a Registry<Object>,
+    resolved: HashMap<&'a RegistryName, BitVec>,
+    stack: Vec<&'a RegistryName>,
+}
+
+impl<'a, Object: RegistryObject> TagResolver<'a, Object> {
+    fn resolve(&mut self, tag: &'a RegistryName) -> Result<(), TagBuildError> {
+        if self.resolved.contains_key(tag) {
+            return Ok(());
+        }
+        if let Some(start) = self.stack.iter().position(|&t| t == tag) {
+            let mut cycle: Vec<RegistryName> = self.stack[start..].iter().map(|&t| t.clone()).collect();
+            cycle.push(tag.clone());
+            return Err(TagBuildError::Cycle(cycle));
+        }
+        self.stack.push(tag);
+        let mut bits = bitvec![0; self.registry.id_upper_bound() as usize];
+        for entry in self.definitions.tags[tag].iter() {
+            match entry {
+                TagEntry::Object(object) => {
+                    let id = self.registry.lookup_name_to_id(object.as_ref()).ok_or_else(|| {
+                        TagBuildError::UnknownObject {
+                            tag: tag.clone(),
+                            object: object.clone(),
+                        }
+                    })?;
+                    bits.set(id.to_bits() as usize, true);
+                }
+                TagEntry::Tag(included) => {
+                    let (included, _) =
+                        self.definitions
+                            .tags
+                            .get_key_value(included)
+                            .ok_or_else(|| TagBuildError::UnknownTag {
+                                tag: tag.clone(),
+                                included: included.clone(),
+                            })?;
+                    self.resolve(included)?;
+                    bits |= &self.resolved[included];
+                }
+            }
+        }
+        self.stack.pop();
+        self.resolved.insert(tag, bits);
+        Ok(())
+    }
+}
+
+/// A resolved set of registry objects, stored as a bitset indexed by the raw ID.
+pub struct Tag<Object> {
+    bits: BitVec,
+    len: usize,
+    _object: PhantomData<fn() -> Object>,
+}
+
+impl<Object> Clone for Tag<Object> {
+    fn clone(&self) -> Self {
+        Self {
+            bits: self.bits.clone(),
+            len: self.len,
+            _object: PhantomData,
+        }
+    }
+}
+
+impl<Object> std::fmt::Debug for Tag<Object> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter().map(|id| id.to_bits())).finish()
+    }
+}
+
+impl<Object> Tag<Object> {
+    fn from_bits(bits: BitVec) -> Self {
+        Self {
+            len: bits.count_ones(),
+            bits,
+            _object: PhantomData,
+        }
+    }
+
+    /// Checks if the object with the given ID is in this tag.
+    #[inline]
+    pub fn contains(&self, id: TypedRegistryId<Object>) -> bool {
+        self.bits.get(id.to_bits() as usize).is_some_and(|bit| *bit)
+    }
+
+    /// Number of objects in the tag.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the tag has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the IDs of all the objects in the tag, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = TypedRegistryId<Object>> + '_ {
+        self.bits
+            .iter_ones()
+            .filter_map(|raw_id| TypedRegistryId::try_from_bits(raw_id as u32))
+    }
+}
+
+/// All the tags of a registry, resolved to object IDs by [`TagDefinitions::build`].
+///
+/// The tags are a snapshot of the registry at build time, and are never updated automatically:
+/// after the registry [changes](Self::is_stale), lookups keep returning the old IDs until the caller rebuilds the tags,
+/// typically by calling [`TagDefinitions::rebuild_if_stale`] after every batch of registry modifications.
+pub struct RegistryTags<Object> {
+    tags: HashMap<RegistryName, Tag<Object>>,
+    generation: u64,
+}
+
+impl<Object> Clone for RegistryTags<Object> {
+    fn clone(&self) -> Self {
+        Self {
+            tags: self.tags.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+impl<Object> std::fmt::Debug for RegistryTags<Object> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.tags.iter()).finish()
+    }
+}
+
+impl<Object> Default for RegistryTags<Object> {
+    /// No tags, always considered stale.
+    fn default() -> Self {
+        Self {
+            tags: HashMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl<Object> RegistryTags<Object> {
+    /// Looks up a tag by name, or returns `None` if it's not defined.
+    pub fn get(&self, tag: RegistryNameRef) -> Option<&Tag<Object>> {
+        self.tags.get(&tag)
+    }
+
+    /// Checks if the object with the given ID is in the given tag, undefined tags contain no objects.
+    #[inline]
+    pub fn contains(&self, tag: RegistryNameRef, id: TypedRegistryId<Object>) -> bool {
+        self.get(tag).is_some_and(|tag| tag.contains(id))
+    }
+
+    /// Iterates over the names of all the tags containing the given object, in no particular order.
+    pub fn tags_of(&self, id: TypedRegistryId<Object>) -> impl Iterator<Item = &RegistryName> {
+        self.tags
+            .iter()
+            .filter(move |(_, tag)| tag.contains(id))
+            .map(|(name, _)| name)
+    }
+
+    /// Number of tags.
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Checks if there are no tags.
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Iterates over all the tags, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&RegistryName, &Tag<Object>)> {
+        self.tags.iter()
+    }
+
+    /// Checks if the registry has changed since the tags were built from it (or if the tags were built from a different registry).
+    pub fn is_stale(&self, registry: &Registry<Object>) -> bool
+    where
+        Object: RegistryObject,
+    {
+        self.generation != registry.generation()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Eq, PartialEq, Debug, Default)]
+    struct DummyObject(RegistryName);
+
+    impl RegistryObject for DummyObject {
+        fn registry_name(&self) -> RegistryNameRef {
+            self.0.as_ref()
+        }
+    }
+
+    fn name(name: &str) -> RegistryName {
+        name.parse().unwrap()
+    }
+
+    fn registry(keys: &[&str]) -> Registry<DummyObject> {
+        let mut reg = Registry::default();
+        for key in keys {
+            reg.push_object(DummyObject(name(key))).unwrap();
+        }
+        reg
+    }
+
+    #[test]
+    pub fn tag_entry_parse() {
+        assert_eq!("oak_log".parse(), Ok(TagEntry::Object(name("gs:oak_log"))));
+        assert_eq!("#mod:logs".parse(), Ok(TagEntry::Tag(name("mod:logs"))));
+        assert_eq!(TagEntry::Tag(name("logs")).to_string(), "#gs:logs");
+        assert!("#".parse::<TagEntry>().is_err());
+        assert!("gs:Log".parse::<TagEntry>().is_err());
+    }
+
+    #[test]
+    pub fn build_nested_tags() {
+        let mut reg = registry(&["oak_log", "birch_log", "dirt", "mod:palm_log"]);
+        let id = |reg: &Registry<DummyObject>, n: &str| reg.lookup_name_to_id(name(n).as_ref()).unwrap();
+
+        let mut defs = TagDefinitions::default();
+        defs.add_ron_str(name("logs"), r##"(values: ["oak_log", "gs:birch_log", "#mod:logs"])"##)
+            .unwrap();
+        defs.add_ron_str(name("mod:logs"), r#"(values: ["mod:palm_log"])"#)
+            .unwrap();
+        defs.add_entry(name("everything"), TagEntry::Tag(name("logs")));
+        defs.add_entry(name("everything"), TagEntry::Object(name("dirt")));
+        defs.add_entry(name("empty"), TagEntry::Tag(name("empty_too")));
+        defs.add(name("empty_too"), TagDefinition::default());
+        assert_eq!(defs.len(), 5);
+
+        let tags = defs.build(&reg).unwrap();
+        let logs = tags.get(name("logs").as_ref()).unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(logs.contains(id(&reg, "oak_log")));
+        assert!(logs.contains(id(&reg, "mod:palm_log")));
+        assert!(!logs.contains(id(&reg, "dirt")));
+        assert_eq!(logs.iter().map(|i| i.to_bits()).collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert_eq!(tags.get(name("everything").as_ref()).unwrap().len(), 4);
+        assert!(tags.get(name("empty").as_ref()).unwrap().is_empty());
+        assert!(tags.contains(name("mod:logs").as_ref(), id(&reg, "mod:palm_log")));
+        assert!(!tags.contains(name("undefined").as_ref(), id(&reg, "dirt")));
+        let mut dirt_tags: Vec<_> = tags.tags_of(id(&reg, "dirt")).collect();
+        dirt_tags.sort();
+        assert_eq!(dirt_tags, vec![&name("everything")]);
+
+        // Adding an object makes the tags stale, they don't know about the new ID
+        assert!(!tags.is_stale(&reg));
+        let mut tags = tags;
+        let spruce = reg.push_object(DummyObject(name("spruce_log"))).unwrap();
+        assert!(tags.is_stale(&reg));
+        assert!(!tags.contains(name("logs").as_ref(), spruce));
+        defs.add(
+            name("logs"),
+            TagDefinition {
+                replace: true,
+                values: vec![TagEntry::Object(name("spruce_log"))],
+            },
+        );
+        assert_eq!(defs.rebuild_if_stale(&mut tags, &reg), Ok(true));
+        assert_eq!(defs.rebuild_if_stale(&mut tags, &reg), Ok(false));
+        assert!(tags.contains(name("logs").as_ref(), spruce));
+        assert!(!tags.contains(name("logs").as_ref(), id(&reg, "oak_log")));
+        assert!(RegistryTags::default().is_stale(&reg));
+
+        // Compaction moves IDs around
+        reg.remove_object(id(&reg, "oak_log"));
+        assert!(tags.is_stale(&reg));
+        defs.rebuild_if_stale(&mut tags, &reg).unwrap();
+        reg.compact();
+        assert!(tags.is_stale(&reg));
+        defs.rebuild_if_stale(&mut tags, &reg).unwrap();
+        assert!(tags.contains(name("logs").as_ref(), id(&reg, "spruce_log")));
+        assert!(tags.contains(name("everything").as_ref(), id(&reg, "dirt")));
+    }
+
+    #[test]
+    pub fn build_errors() {
+        let reg = registry(&["a", "b"]);
+        let mut defs = TagDefinitions::default();
+        defs.add_entry(name("x"), TagEntry::Object(name("c")));
+        assert_eq!(
+            defs.build(&reg).unwrap_err(),
+            TagBuildError::UnknownObject {
+                tag: name("x"),
+                object: name("c")
+            }
+        );
+
+        let mut defs = TagDefinitions::default();
+        defs.add_entry(name("x"), TagEntry::Tag(name("y")));
+        assert_eq!(
+            defs.build(&reg).unwrap_err(),
+            TagBuildError::UnknownTag {
+                tag: name("x"),
+                included: name("y")
+            }
+        );
+
+        let mut defs = TagDefinitions::default();
+        defs.add_entry(name("x"), TagEntry::Tag(name("y")));
+        defs.add_entry(name("y"), TagEntry::Object(name("a")));
+        defs.add_entry(name("y"), TagEntry::Tag(name("x")));
+        let TagBuildError::Cycle(cycle) = defs.build(&reg).unwrap_err() else {
+            panic!("Expected a cycle error");
+        };
+        assert_eq!(cycle.len(), 3);
+        assert_eq!(cycle.first(), cycle.last());
+        let err = TagBuildError::Cycle(vec![name("x"), name("y"), name("x")]);
+        assert_eq!(
+            err.to_string(),
+            "Tags include each other in a cycle: #gs:x -> #gs:y -> #gs:x"
+        );
+    }
+
+    #[test]
+    pub fn load_tag_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("gs/logs.ron", r##"(values: ["oak_log", "#gs:trees/palms"])"##);
+        write("gs/trees/palms.ron", r#"(values: ["mod:palm_log"])"#);
+        write(
+            "mod/logs.ron",
+            "(\n    replace: true,\n    values: [\"mod:palm_log\"],\n)",
+        );
+        write("gs/readme.txt", "Not a tag");
+
+        let mut defs = TagDefinitions::default();
+        assert_eq!(defs.load_dir(dir.path()).unwrap(), 3);
+        assert_eq!(
+            defs.get(name("logs").as_ref()),
+            Some(&[TagEntry::Object(name("oak_log")), TagEntry::Tag(name("trees/palms"))][..])
+        );
+        let reg = registry(&["oak_log", "mod:palm_log"]);
+        let tags = defs.build(&reg).unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags.get(name("logs").as_ref()).unwrap().len(), 2);
+
+        // Files sorted before the broken one are not added either
+        write("mod/broken.ron", "(\n    values: [\"oak_log\",\n    oops\n)");
+        let loaded = defs.clone();
+        let err = defs.load_dir(dir.path()).unwrap_err();
+        assert!(matches!(err, TagLoadError::Parse { ref path, .. } if path.ends_with("mod/broken.ron")));
+        assert!(err.to_string().contains("broken.ron:3:"), "{err}");
+        assert_eq!(defs, loaded);
+        let mut fresh = TagDefinitions::default();
+        assert!(fresh.load_dir(dir.path()).is_err());
+        assert!(fresh.is_empty());
+
+        std::fs::remove_file(dir.path().join("mod/broken.ron")).unwrap();
+        write("Bad/logs.ron", "(values: [])");
+        let err = defs.load_dir(dir.path()).unwrap_err();
+        assert!(matches!(err, TagLoadError::InvalidName { .. }), "{err}");
+        assert!(matches!(
+            defs.load_dir(&dir.path().join("missing")),
+            Err(TagLoadError::Io { .. })
+        ));
+    }
+}
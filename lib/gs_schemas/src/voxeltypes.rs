@@ -1,8 +1,15 @@
 //! Descriptors for in-game voxel/block types.
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Formatter};
 
 use bytemuck::{Pod, TransparentWrapper, Zeroable};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::chunk::BlockLight;
+use crate::chunk_storage::PaletteStorage;
+use crate::coordinates::{Direction, Faces};
+use crate::registry::{Registry, RegistryIdRemap, RegistryName, RegistryNameRef, RegistryObject, TypedRegistryId};
 
 /**
  * A Block identifier used to uniquely identify a registered block variant.
@@ -28,30 +35,411 @@ use serde::{Deserialize, Serialize};
 #[repr(transparent)]
 pub struct BlockId(u64);
 
+/// The ID of a [`BlockDefinition`] in the block registry, as stored in the [`BlockId`] bits.
+pub type BlockRegistryId = TypedRegistryId<BlockDefinition>;
+
+/// Shape identifier of a block, cached in the [`BlockId`] bits (6 bits wide).
+#[repr(transparent)]
+#[derive(Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, Zeroable, Pod)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct BlockShape(u8);
+
+/// How the block should be rendered, cached in the [`BlockId`] bits (2 bits wide).
+#[repr(u8)]
+#[derive(Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum RenderMode {
+    /// Not rendered at all, e.g. air.
+    #[default]
+    Invisible = 0,
+    /// Fully opaque, hides the faces of neighbouring blocks.
+    Opaque = 1,
+    /// Either fully opaque or fully transparent per-pixel, e.g. leaves.
+    Cutout = 2,
+    /// Partially transparent, needs to be sorted and blended, e.g. glass or water.
+    Translucent = 3,
+}
+
+/// Errors from constructing a [`BlockId`] or its property types from out-of-range bits.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+pub enum BlockIdBitsError {
+    /// The shape id doesn't fit in 6 bits.
+    #[error("Block shape id {0} is outside of the 0..=63 range")]
+    ShapeOutOfRange(u8),
+    /// The solid sides mask doesn't fit in 6 bits.
+    #[error("Block solid sides mask {0:#010b} has bits set outside of the 6 lowest bits")]
+    SolidSidesOutOfRange(u8),
+    /// The render mode doesn't fit in 2 bits.
+    #[error("Block render mode {0} is outside of the 0..=3 range")]
+    RenderModeOutOfRange(u8),
+}
+
+/// The registered definition of a block variant, the source of truth for the properties cached in [`BlockId`]s.
+///
+/// Usually loaded from data files by [`crate::block_loader`], where all fields except the name can be omitted to get a plain opaque cube.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockDefinition {
+    /// The unique name of the block.
+    pub name: RegistryName,
+    /// The shape of the block.
+    #[serde(default)]
+    pub shape: BlockShape,
+    /// Which sides of the block fully cover their neighbours, written as a list of [`Direction`]s.
+    #[serde(default = "default_solid_sides", with = "faces_as_directions")]
+    pub solid_sides: Faces,
+    /// How the block should be rendered.
+    #[serde(default = "default_render_mode")]
+    pub render_mode: RenderMode,
+    /// The light emitted by the block, written as an `(r, g, b)` tuple with channels in `0..=31`.
+    #[serde(default, with = "light_as_rgb")]
+    pub light_emission: BlockLight,
+    /// How much light is lost when passing through the block, [`BlockLight::MAX_CHANNEL`] or more blocks all light.
+    #[serde(default = "default_opacity")]
+    pub opacity: u8,
+    /// The textures of the block faces.
+    #[serde(default)]
+    pub textures: BlockTextures,
+    /// The tags the block is a part of, see [`crate::registry_tags`].
+    #[serde(default)]
+    pub tags: Vec<RegistryName>,
+}
+
+const fn default_solid_sides() -> Faces {
+    Faces::ALL
+}
+
+const fn default_render_mode() -> RenderMode {
+    RenderMode::Opaque
+}
+
+const fn default_opacity() -> u8 {
+    BlockLight::MAX_CHANNEL
+}
+
+/// (De)serializes [`Faces`] as a sequence of [`Direction`]s, more readable than the bitmask in data files.
+mod faces_as_directions {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::coordinates::{Direction, Faces};
+
+    pub fn serialize<S: Serializer>(faces: &Faces, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(faces.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Faces, D::Error> {
+        Ok(Vec::<Direction>::deserialize(deserializer)?.into_iter().collect())
+    }
+}
+
+/// (De)serializes [`BlockLight`] as an `(r, g, b)` tuple, more readable than the packed bits in data files.
+mod light_as_rgb {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::chunk::BlockLight;
+
+    pub fn serialize<S: Serializer>(light: &BlockLight, serializer: S) -> Result<S::Ok, S::Error> {
+        (light.red(), light.green(), light.blue()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BlockLight, D::Error> {
+        let (red, green, blue) = <(u8, u8, u8)>::deserialize(deserializer)?;
+        if red.max(green).max(blue) > BlockLight::MAX_CHANNEL {
+            return Err(serde::de::Error::custom(format_args!(
+                "light channels must be in the 0..={} range, got ({red}, {green}, {blue})",
+                BlockLight::MAX_CHANNEL
+            )));
+        }
+        Ok(BlockLight::new(red, green, blue))
+    }
+}
+
+/// Texture references for the faces of a block, from the least to the most specific: `all`, then `top`/`bottom`/`sides`, then `faces`.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockTextures {
+    /// The texture of all faces without a more specific texture.
+    pub all: Option<RegistryName>,
+    /// The texture of the [`Direction::PosY`] face.
+    pub top: Option<RegistryName>,
+    /// The texture of the [`Direction::NegY`] face.
+    pub bottom: Option<RegistryName>,
+    /// The texture of the 4 horizontal faces.
+    pub sides: Option<RegistryName>,
+    /// Per-face textures, overriding all the other fields.
+    pub faces: BTreeMap<Direction, RegistryName>,
+}
+
+impl BlockTextures {
+    /// Constructs textures using the same texture for all faces.
+    pub fn uniform(texture: RegistryName) -> Self {
+        Self {
+            all: Some(texture),
+            ..Default::default()
+        }
+    }
+
+    /// The texture of the given face, or `None` if no texture applies to it.
+    pub fn face(&self, face: Direction) -> Option<&RegistryName> {
+        let axis_texture = match face {
+            Direction::PosY => self.top.as_ref(),
+            Direction::NegY => self.bottom.as_ref(),
+            _ => self.sides.as_ref(),
+        };
+        self.faces.get(&face).or(axis_texture).or(self.all.as_ref())
+    }
+}
+
+/// How to treat [`BlockId`]s whose cached property bits don't match the registered [`BlockDefinition`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BlockIdValidationMode {
+    /// Replace the cached bits with the ones from the registry.
+    Rewrite,
+    /// Fail with a [`BlockIdValidationError::PropertyMismatch`] error.
+    Reject,
+}
+
+/// Errors from validating [`BlockId`]s against the block registry.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+pub enum BlockIdValidationError {
+    /// The registry id is not present in the registry.
+    #[error("Block {0:?} is not registered")]
+    Unregistered(BlockId),
+    /// The cached property bits don't match the registered definition.
+    #[error("Block {found:?} doesn't match the registered properties {expected:?}")]
+    PropertyMismatch {
+        /// The invalid block id.
+        found: BlockId,
+        /// The block id with the properties from the registry.
+        expected: BlockId,
+    },
+}
+
+/// Error when a [`BlockId`]'s registry ID is not present in a [`RegistryIdRemap`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Error)]
+#[error("Block {0:?} has no mapping in the registry ID remap")]
+pub struct UnmappedBlockIdError(pub BlockId);
+
+impl BlockDefinition {
+    /// Constructs a definition of a plain, untextured opaque cube, the same as a data file with just the name.
+    pub fn new(name: RegistryName) -> Self {
+        Self {
+            name,
+            shape: BlockShape::default(),
+            solid_sides: default_solid_sides(),
+            render_mode: default_render_mode(),
+            light_emission: BlockLight::ZERO,
+            opacity: default_opacity(),
+            textures: BlockTextures::default(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Constructs the canonical block id for this definition registered at the given id.
+    pub const fn block_id(&self, id: BlockRegistryId) -> BlockId {
+        BlockId::builder(id)
+            .shape(self.shape)
+            .solid_sides(self.solid_sides)
+            .render_mode(self.render_mode)
+            .build()
+    }
+}
+
+impl RegistryObject for BlockDefinition {
+    fn registry_name(&self) -> RegistryNameRef {
+        self.name.as_ref()
+    }
+}
+
+const SHAPE_SHIFT: u32 = 0;
+const SHAPE_MASK: u64 = 0b111111;
+const SOLID_SIDES_SHIFT: u32 = 6;
+const SOLID_SIDES_MASK: u64 = 0b111111;
+const RENDER_MODE_SHIFT: u32 = 12;
+const RENDER_MODE_MASK: u64 = 0b11;
+const REGISTRY_ID_SHIFT: u32 = 32;
+const REGISTRY_ID_MASK: u64 = 0xFFFF_FFFF;
+
+impl BlockShape {
+    /// The largest shape id that fits in the [`BlockId`] bits.
+    pub const MAX: Self = Self(SHAPE_MASK as u8);
+
+    /// Constructs a shape id, or returns an error if it doesn't fit in 6 bits.
+    pub const fn try_new(id: u8) -> Result<Self, BlockIdBitsError> {
+        if id as u64 > SHAPE_MASK {
+            Err(BlockIdBitsError::ShapeOutOfRange(id))
+        } else {
+            Ok(Self(id))
+        }
+    }
+
+    /// The raw shape id.
+    pub const fn id(self) -> u8 {
+        self.0
+    }
+}
+
+impl TryFrom<u8> for BlockShape {
+    type Error = BlockIdBitsError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        Self::try_new(id)
+    }
+}
+
+impl From<BlockShape> for u8 {
+    fn from(shape: BlockShape) -> Self {
+        shape.0
+    }
+}
+
+impl RenderMode {
+    /// All the render modes, in discriminant order.
+    pub const ALL: [RenderMode; 4] = [
+        RenderMode::Invisible,
+        RenderMode::Opaque,
+        RenderMode::Cutout,
+        RenderMode::Translucent,
+    ];
+
+    /// Converts the raw 2-bit value into a render mode, or returns an error if it's out of range.
+    pub const fn try_from_bits(bits: u8) -> Result<Self, BlockIdBitsError> {
+        if bits as u64 > RENDER_MODE_MASK {
+            Err(BlockIdBitsError::RenderModeOutOfRange(bits))
+        } else {
+            Ok(Self::ALL[bits as usize])
+        }
+    }
+
+    /// The raw 2-bit value of this render mode.
+    pub const fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
 impl BlockId {
-    pub fn from_bits(registry_id: u32, shape_id: u8, solid_sides: u8, render_mode: u8) -> Self {
+    /// Packs the given registry id and property bits into a block id, silently masking out any out-of-range property bits.
+    /// Prefer [`BlockId::try_from_bits`] or [`BlockId::builder`] to catch mistakes.
+    pub const fn from_bits(registry_id: u32, shape_id: u8, solid_sides: u8, render_mode: u8) -> Self {
         Self(
-            (registry_id as u64) << 3
-                | (shape_id & 0b111111) as u64
-                | ((solid_sides & 0b111111) as u64) << 6
-                | ((render_mode & 0b11) as u64) << 12,
+            (registry_id as u64) << REGISTRY_ID_SHIFT
+                | ((shape_id as u64) & SHAPE_MASK) << SHAPE_SHIFT
+                | ((solid_sides as u64) & SOLID_SIDES_MASK) << SOLID_SIDES_SHIFT
+                | ((render_mode as u64) & RENDER_MODE_MASK) << RENDER_MODE_SHIFT,
         )
     }
 
-    pub fn registry_id_bits(self) -> u32 {
-        ((self.0 >> 32) & 0xFFFF_FFFF) as u32
+    /// Packs the given registry id and property bits into a block id, or returns an error if any of the bits are out of range.
+    pub const fn try_from_bits(
+        registry_id: u32,
+        shape_id: u8,
+        solid_sides: u8,
+        render_mode: u8,
+    ) -> Result<Self, BlockIdBitsError> {
+        if shape_id as u64 > SHAPE_MASK {
+            return Err(BlockIdBitsError::ShapeOutOfRange(shape_id));
+        }
+        if solid_sides as u64 > SOLID_SIDES_MASK {
+            return Err(BlockIdBitsError::SolidSidesOutOfRange(solid_sides));
+        }
+        if render_mode as u64 > RENDER_MODE_MASK {
+            return Err(BlockIdBitsError::RenderModeOutOfRange(render_mode));
+        }
+        Ok(Self::from_bits(registry_id, shape_id, solid_sides, render_mode))
     }
 
-    pub fn shape_id_bits(self) -> u8 {
-        (self.0 & 0b111111) as u8
+    /// Starts building a block id for the given registry object, with all properties set to their defaults.
+    pub const fn builder(registry_id: BlockRegistryId) -> BlockIdBuilder {
+        BlockIdBuilder::new(registry_id)
     }
 
-    pub fn solid_sides_bits(self) -> u8 {
-        ((self.0 >> 6) & 0b111111) as u8
+    /// The raw 64-bit representation.
+    pub const fn to_raw(self) -> u64 {
+        self.0
     }
 
-    pub fn render_mode_bits(self) -> u8 {
-        ((self.0 >> 12) & 0b11) as u8
+    /// Reconstructs a block id from the raw 64-bit representation, without any validation.
+    pub const fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// The raw registry id bits, 0 if the block is not registered.
+    pub const fn registry_id_bits(self) -> u32 {
+        ((self.0 >> REGISTRY_ID_SHIFT) & REGISTRY_ID_MASK) as u32
+    }
+
+    /// The raw 6-bit shape id.
+    pub const fn shape_id_bits(self) -> u8 {
+        ((self.0 >> SHAPE_SHIFT) & SHAPE_MASK) as u8
+    }
+
+    /// The raw 6-bit solid sides mask, see [`Faces`].
+    pub const fn solid_sides_bits(self) -> u8 {
+        ((self.0 >> SOLID_SIDES_SHIFT) & SOLID_SIDES_MASK) as u8
+    }
+
+    /// The raw 2-bit render mode, see [`RenderMode`].
+    pub const fn render_mode_bits(self) -> u8 {
+        ((self.0 >> RENDER_MODE_SHIFT) & RENDER_MODE_MASK) as u8
+    }
+
+    /// The registry id, or `None` if the bits are zero.
+    pub const fn registry_id(self) -> Option<BlockRegistryId> {
+        BlockRegistryId::try_from_bits(self.registry_id_bits())
+    }
+
+    /// The cached shape id.
+    pub const fn shape(self) -> BlockShape {
+        BlockShape(self.shape_id_bits())
+    }
+
+    /// The cached set of solid sides.
+    pub const fn solid_sides(self) -> Faces {
+        Faces::from_bits_truncate(self.solid_sides_bits())
+    }
+
+    /// The cached render mode.
+    pub const fn render_mode(self) -> RenderMode {
+        RenderMode::ALL[self.render_mode_bits() as usize]
+    }
+
+    /// Returns a copy of this block id pointing at a different registry object, keeping the cached property bits.
+    pub const fn with_registry_id(self, id: BlockRegistryId) -> Self {
+        Self((self.0 & !(REGISTRY_ID_MASK << REGISTRY_ID_SHIFT)) | ((id.to_bits() as u64) << REGISTRY_ID_SHIFT))
+    }
+
+    /// Translates the registry id according to the remap, keeping the cached property bits.
+    /// The all-zero default block id (empty space) is kept as-is.
+    pub fn remap(self, remap: &RegistryIdRemap) -> Result<Self, UnmappedBlockIdError> {
+        match self.registry_id() {
+            None if self == BlockId::default() => Ok(self),
+            None => Err(UnmappedBlockIdError(self)),
+            Some(id) => remap
+                .get(id.untyped())
+                .map(|new_id| self.with_registry_id(BlockRegistryId::from_untyped(new_id)))
+                .ok_or(UnmappedBlockIdError(self)),
+        }
+    }
+
+    /// Checks the cached property bits against the registered definition, returning the canonical block id.
+    /// The all-zero default block id (empty space) is always valid.
+    pub fn validate(
+        self,
+        registry: &Registry<BlockDefinition>,
+        mode: BlockIdValidationMode,
+    ) -> Result<BlockId, BlockIdValidationError> {
+        if self == BlockId::default() {
+            return Ok(self);
+        }
+        let id = self.registry_id().ok_or(BlockIdValidationError::Unregistered(self))?;
+        let definition = registry
+            .lookup_id_to_object(id)
+            .ok_or(BlockIdValidationError::Unregistered(self))?;
+        let expected = definition.block_id(id);
+        if self == expected || mode == BlockIdValidationMode::Rewrite {
+            Ok(expected)
+        } else {
+            Err(BlockIdValidationError::PropertyMismatch { found: self, expected })
+        }
     }
 }
 
@@ -67,3 +455,222 @@ impl Debug for BlockId {
         )
     }
 }
+
+/// Applies a registry ID remap to every block in the storage, by only rewriting the palette.
+/// If any block has no mapping, the storage is left unmodified and the error is returned.
+pub fn remap_palette_block_ids(
+    storage: &mut PaletteStorage<BlockId>,
+    remap: &RegistryIdRemap,
+) -> Result<(), UnmappedBlockIdError> {
+    storage.try_map_palette(|block| block.remap(remap))
+}
+
+/// A builder for [`BlockId`]s with typed, range-checked properties.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct BlockIdBuilder {
+    registry_id: BlockRegistryId,
+    shape: BlockShape,
+    solid_sides: Faces,
+    render_mode: RenderMode,
+}
+
+impl BlockIdBuilder {
+    /// Starts building a block id for the given registry object, with all properties set to their defaults.
+    pub const fn new(registry_id: BlockRegistryId) -> Self {
+        Self {
+            registry_id,
+            shape: BlockShape(0),
+            solid_sides: Faces::NONE,
+            render_mode: RenderMode::Invisible,
+        }
+    }
+
+    /// Sets the shape.
+    pub const fn shape(mut self, shape: BlockShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Sets the solid sides.
+    pub const fn solid_sides(mut self, solid_sides: Faces) -> Self {
+        self.solid_sides = solid_sides;
+        self
+    }
+
+    /// Sets the render mode.
+    pub const fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    /// Sets the shape from a raw id, checking the range.
+    pub const fn try_shape_bits(self, shape_id: u8) -> Result<Self, BlockIdBitsError> {
+        match BlockShape::try_new(shape_id) {
+            Ok(shape) => Ok(self.shape(shape)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the solid sides from a raw mask, checking the range.
+    pub const fn try_solid_sides_bits(self, solid_sides: u8) -> Result<Self, BlockIdBitsError> {
+        match Faces::try_from_bits(solid_sides) {
+            Ok(faces) => Ok(self.solid_sides(faces)),
+            Err(_) => Err(BlockIdBitsError::SolidSidesOutOfRange(solid_sides)),
+        }
+    }
+
+    /// Sets the render mode from a raw value, checking the range.
+    pub const fn try_render_mode_bits(self, render_mode: u8) -> Result<Self, BlockIdBitsError> {
+        match RenderMode::try_from_bits(render_mode) {
+            Ok(mode) => Ok(self.render_mode(mode)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Packs the properties into a block id.
+    pub const fn build(self) -> BlockId {
+        BlockId::from_bits(
+            self.registry_id.to_bits(),
+            self.shape.id(),
+            self.solid_sides.bits(),
+            self.render_mode.bits(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+    use crate::coordinates::Direction;
+
+    #[test]
+    fn block_id_property_roundtrip() {
+        let reg = BlockRegistryId::try_from(0xDEAD_BEEF).unwrap();
+        for shape in 0..=BlockShape::MAX.id() {
+            for sides in 0..=Faces::ALL.bits() {
+                for mode in RenderMode::ALL {
+                    let id = BlockId::try_from_bits(reg.to_bits(), shape, sides, mode.bits()).unwrap();
+                    assert_eq!(id.registry_id(), Some(reg));
+                    assert_eq!(id.shape_id_bits(), shape);
+                    assert_eq!(id.solid_sides_bits(), sides);
+                    assert_eq!(id.render_mode_bits(), mode.bits());
+                    assert_eq!(id.render_mode(), mode);
+                    assert_eq!(id.solid_sides().bits(), sides);
+                    let built = BlockId::builder(reg)
+                        .try_shape_bits(shape)
+                        .unwrap()
+                        .try_solid_sides_bits(sides)
+                        .unwrap()
+                        .render_mode(mode)
+                        .build();
+                    assert_eq!(built, id);
+                    assert_eq!(BlockId::from_raw(id.to_raw()), id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn block_id_range_checks() {
+        assert_eq!(
+            BlockId::try_from_bits(1, 64, 0, 0),
+            Err(BlockIdBitsError::ShapeOutOfRange(64))
+        );
+        assert_eq!(
+            BlockId::try_from_bits(1, 0, 0b1000000, 0),
+            Err(BlockIdBitsError::SolidSidesOutOfRange(0b1000000))
+        );
+        assert_eq!(
+            BlockId::try_from_bits(1, 0, 0, 4),
+            Err(BlockIdBitsError::RenderModeOutOfRange(4))
+        );
+        let builder = BlockId::builder(BlockRegistryId::try_from(1).unwrap());
+        assert!(builder.try_shape_bits(64).is_err());
+        assert!(builder.try_solid_sides_bits(0xFF).is_err());
+        assert!(builder.try_render_mode_bits(4).is_err());
+        // The unchecked variant masks the bits out instead
+        assert_eq!(
+            BlockId::from_bits(1, 0xFF, 0xFF, 0xFF),
+            BlockId::from_bits(1, 63, 63, 3)
+        );
+        assert_eq!(BlockId::default().registry_id(), None);
+        assert_eq!(BlockId::default().render_mode(), RenderMode::Invisible);
+        assert!(BlockId::default().solid_sides().is_empty());
+    }
+
+    #[test]
+    fn block_id_validation() {
+        let mut registry: Registry<BlockDefinition> = Registry::default();
+        let stone = BlockDefinition::new(RegistryName::geosia("stone"));
+        let stone_id = registry.push_object(stone.clone()).unwrap();
+        let canonical = stone.block_id(stone_id);
+        let bad_bits = BlockId::builder(stone_id).render_mode(RenderMode::Cutout).build();
+        let unregistered = BlockId::builder(BlockRegistryId::try_from(7).unwrap()).build();
+
+        for mode in [BlockIdValidationMode::Rewrite, BlockIdValidationMode::Reject] {
+            assert_eq!(canonical.validate(&registry, mode), Ok(canonical));
+            assert_eq!(BlockId::default().validate(&registry, mode), Ok(BlockId::default()));
+            assert_eq!(
+                unregistered.validate(&registry, mode),
+                Err(BlockIdValidationError::Unregistered(unregistered))
+            );
+        }
+        assert_eq!(
+            bad_bits.validate(&registry, BlockIdValidationMode::Rewrite),
+            Ok(canonical)
+        );
+        assert_eq!(
+            bad_bits.validate(&registry, BlockIdValidationMode::Reject),
+            Err(BlockIdValidationError::PropertyMismatch {
+                found: bad_bits,
+                expected: canonical
+            })
+        );
+    }
+
+    #[test]
+    fn block_id_palette_remap() {
+        use crate::chunk_storage::ChunkStorage;
+        use crate::coordinates::{InChunkPos, InChunkRange};
+
+        let id = |v: u32| BlockRegistryId::try_from(v).unwrap();
+        let a = BlockId::builder(id(1)).render_mode(RenderMode::Opaque).build();
+        let b = BlockId::builder(id(2)).solid_sides(Faces::ALL).build();
+        let c = BlockId::builder(id(3)).build();
+        let mut storage: PaletteStorage<BlockId> = PaletteStorage::default();
+        storage.fill(InChunkRange::WHOLE_CHUNK, a);
+        storage.put(InChunkPos::ZERO, b);
+        storage.put(InChunkPos::ONE, BlockId::default());
+
+        let mut remap = RegistryIdRemap::default();
+        remap.insert(id(1).untyped(), id(2).untyped());
+        remap.insert(id(2).untyped(), id(1).untyped());
+        let original = storage.clone();
+        storage.put(InChunkPos::MAX, c);
+        assert_eq!(
+            remap_palette_block_ids(&mut storage, &remap),
+            Err(UnmappedBlockIdError(c))
+        );
+        storage.put(InChunkPos::MAX, a);
+        assert_eq!(storage.iter().collect::<Vec<_>>(), original.iter().collect::<Vec<_>>());
+
+        remap_palette_block_ids(&mut storage, &remap).unwrap();
+        assert_eq!(storage.get_copy(InChunkPos::ZERO), b.with_registry_id(id(1)));
+        assert_eq!(storage.get_copy(InChunkPos::ONE), BlockId::default());
+        assert_eq!(storage.get_copy(InChunkPos::MAX), a.with_registry_id(id(2)));
+        assert_eq!(storage.get_copy(InChunkPos::MAX).render_mode(), RenderMode::Opaque);
+    }
+
+    #[quickcheck]
+    fn block_id_registry_roundtrip(registry_id: u32, shape: u8, sides: u8, mode: u8) -> bool {
+        let id = BlockId::from_bits(registry_id, shape, sides, mode);
+        id.registry_id_bits() == registry_id
+            && id.shape_id_bits() == shape & 0b111111
+            && id.solid_sides_bits() == sides & 0b111111
+            && id.render_mode_bits() == mode & 0b11
+            && id.registry_id().map_or(0, BlockRegistryId::to_bits) == registry_id
+            && id.solid_sides().contains(Direction::PosX) == (sides & 1 != 0)
+    }
+}
@@ -0,0 +1,676 @@
+//! World save directories and their metadata.
+//!
+//! A world save directory is laid out as:
+//! - `world.ron`: the [`WorldMeta`] manifest
+//! - `session.lock`: locked while the world is open, so that only one process can open it at a time
+//! - `dimensions/<namespace>/<key>/`: the [region files](crate::region) of every dimension
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use ron::Value;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::AbsBlockPos;
+use crate::migration::{MigrationError, Migrations, WORLD_META_MIGRATIONS};
+use crate::region::{sync_parent_dir, RegionError, RegionStore};
+use crate::registry::{RegistryName, RegistryNameRef, RegistrySnapshot};
+
+/// The current version of the [`WorldMeta`] format.
+pub const WORLD_META_VERSION: u32 = 1;
+/// Name of the metadata file in a world save directory.
+pub const WORLD_META_FILE: &str = "world.ron";
+/// Name of the lock file in a world save directory.
+pub const WORLD_LOCK_FILE: &str = "session.lock";
+/// Name of the directory containing the per-dimension data in a world save directory.
+pub const WORLD_DIMENSIONS_DIR: &str = "dimensions";
+
+/// The manifest of a saved world, stored in [`WORLD_META_FILE`].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldMeta {
+    /// The format version this metadata was written with, always [`WORLD_META_VERSION`] after loading.
+    pub format_version: u32,
+    /// The world generation seed.
+    pub seed: u64,
+    /// The block IDs used by the chunks saved in this world.
+    pub block_registry: RegistrySnapshot,
+    /// Where new players appear.
+    pub spawn: SpawnPoint,
+    /// Number of game ticks elapsed in the world.
+    pub game_time: u64,
+    /// The dimensions of the world, each with separate chunk data.
+    pub dimensions: Vec<DimensionMeta>,
+}
+
+/// The place where new players appear in a world.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnPoint {
+    /// The dimension of the spawn point, one of [`WorldMeta::dimensions`].
+    pub dimension: RegistryName,
+    /// The block position of the spawn point.
+    pub position: AbsBlockPos,
+}
+
+/// Metadata of a single dimension of a world.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DimensionMeta {
+    /// The unique name of the dimension, also determining its data directory.
+    pub name: RegistryName,
+}
+
+/// Possible inconsistencies in [`WorldMeta`].
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
+pub enum WorldMetaError {
+    /// The metadata is not in the current format version.
+    #[error("The world metadata has format version {0}, expected {WORLD_META_VERSION}")]
+    WrongVersion(u32),
+    /// The world has no dimensions.
+    #[error("The world has no dimensions")]
+    NoDimensions,
+    /// Two dimensions have the same name.
+    #[error("Dimension {0} is listed more than once")]
+    DuplicateDimension(RegistryName),
+    /// The dimension name can't be used as a path inside of the save directory, e.g. it has `..` or empty `/`-separated segments.
+    #[error("Dimension name {0} is not a valid relative directory path")]
+    InvalidDimensionName(RegistryName),
+    /// The spawn point is in a dimension that doesn't exist.
+    #[error("The spawn point is in dimension {0}, which is not one of the world's dimensions")]
+    UnknownSpawnDimension(RegistryName),
+}
+
+/// Possible errors from creating, opening and validating world save directories.
+#[derive(Debug, Error)]
+pub enum WorldSaveError {
+    /// A file or directory of the world save could not be accessed.
+    #[error("I/O error accessing world data at {}: {source}", path.display())]
+    Io {
+        /// The path of the file or directory.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// Another process (or another [`WorldSave`] in this process) has the world open.
+    #[error("The world at {} is already open", path.display())]
+    Locked {
+        /// The world save directory.
+        path: PathBuf,
+    },
+    /// A world can't be created where one already exists.
+    #[error("A world already exists at {}", path.display())]
+    AlreadyExists {
+        /// The world save directory.
+        path: PathBuf,
+    },
+    /// The directory has no world metadata file.
+    #[error("There is no world at {}", path.display())]
+    NotAWorld {
+        /// The world save directory.
+        path: PathBuf,
+    },
+    /// The metadata file is not valid RON, or doesn't match the [`WorldMeta`] schema.
+    #[error("Could not parse the world metadata {}: {source}", path.display())]
+    Parse {
+        /// The path of the metadata file.
+        path: PathBuf,
+        /// The parsing error.
+        source: ron::Error,
+    },
+    /// The metadata could not be upgraded to the current format version, e.g. it was written by a newer version of the game.
+    #[error("Could not upgrade the world metadata {}: {source}", path.display())]
+    Migration {
+        /// The path of the metadata file.
+        path: PathBuf,
+        /// The upgrade error.
+        source: MigrationError,
+    },
+    /// The chunk data of a dimension could not be accessed.
+    #[error(transparent)]
+    Region(#[from] RegionError),
+    /// The metadata is inconsistent.
+    #[error("Invalid world metadata {}: {source}", path.display())]
+    Invalid {
+        /// The path of the metadata file.
+        path: PathBuf,
+        /// The inconsistency.
+        source: WorldMetaError,
+    },
+}
+
+impl WorldMeta {
+    /// Constructs the metadata of a new world with a single dimension, with the spawn point at the origin of that dimension.
+    pub fn new(seed: u64, block_registry: RegistrySnapshot, dimension: RegistryName) -> Self {
+        Self {
+            format_version: WORLD_META_VERSION,
+            seed,
+            block_registry,
+            spawn: SpawnPoint {
+                dimension: dimension.clone(),
+                position: AbsBlockPos::ZERO,
+            },
+            game_time: 0,
+            dimensions: vec![DimensionMeta { name: dimension }],
+        }
+    }
+
+    /// Checks the metadata for inconsistencies.
+    pub fn validate(&self) -> Result<(), WorldMetaError> {
+        if self.format_version != WORLD_META_VERSION {
+            return Err(WorldMetaError::WrongVersion(self.format_version));
+        }
+        if self.dimensions.is_empty() {
+            return Err(WorldMetaError::NoDimensions);
+        }
+        let mut names = BTreeSet::new();
+        for dimension in self.dimensions.iter() {
+            let name = dimension.name.as_ref();
+            if !is_relative_dir_path(name.ns.as_str()) || !is_relative_dir_path(name.key.as_str()) {
+                return Err(WorldMetaError::InvalidDimensionName(dimension.name.clone()));
+            }
+            if !names.insert(&dimension.name) {
+                return Err(WorldMetaError::DuplicateDimension(dimension.name.clone()));
+            }
+        }
+        if !names.contains(&self.spawn.dimension) {
+            return Err(WorldMetaError::UnknownSpawnDimension(self.spawn.dimension.clone()));
+        }
+        Ok(())
+    }
+
+    /// Looks up a dimension by name.
+    pub fn dimension(&self, name: RegistryNameRef) -> Option<&DimensionMeta> {
+        self.dimensions.iter().find(|dimension| dimension.name.as_ref() == name)
+    }
+
+    /// Parses the RON metadata of any supported format version, upgrading it to the current version with [`WORLD_META_MIGRATIONS`].
+    /// Returns the metadata and the format version it was stored with.
+    ///
+    /// The metadata is not [validated](Self::validate).
+    pub fn from_ron_upgrading(path: &Path, source: &str) -> Result<(Self, u32), WorldSaveError> {
+        Self::from_ron_with_migrations(path, source, &WORLD_META_MIGRATIONS)
+    }
+
+    fn from_ron_with_migrations(
+        path: &Path,
+        source: &str,
+        migrations: &Migrations<ron::Map>,
+    ) -> Result<(Self, u32), WorldSaveError> {
+        let parse_error = |source| WorldSaveError::Parse {
+            path: path.to_owned(),
+            source,
+        };
+        let migration_error = |source| WorldSaveError::Migration {
+            path: path.to_owned(),
+            source,
+        };
+        let Value::Map(mut map) = ron::from_str::<Value>(source).map_err(|e| parse_error(e.code))? else {
+            return Err(migration_error(MigrationError::MissingVersion {
+                format: migrations.format,
+            }));
+        };
+        let version = migrations.migrate(&mut map).map_err(migration_error)?;
+        let meta = Value::Map(map).into_rust::<Self>().map_err(parse_error)?;
+        Ok((meta, version))
+    }
+
+    /// Serializes the metadata as pretty-printed RON.
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("World metadata is always serializable")
+    }
+}
+
+/// An open world save directory, locked for exclusive use by this process until dropped.
+#[derive(Debug)]
+pub struct WorldSave {
+    dir: PathBuf,
+    meta: WorldMeta,
+    opened_version: u32,
+    _lock: File,
+}
+
+impl WorldSave {
+    /// Creates a new world in the given directory, which is created if needed.
+    pub fn create(dir: impl Into<PathBuf>, meta: WorldMeta) -> Result<Self, WorldSaveError> {
+        let dir = dir.into();
+        let meta_path = dir.join(WORLD_META_FILE);
+        meta.validate().map_err(|source| WorldSaveError::Invalid {
+            path: meta_path.clone(),
+            source,
+        })?;
+        std::fs::create_dir_all(&dir).map_err(|source| WorldSaveError::Io {
+            path: dir.clone(),
+            source,
+        })?;
+        let lock = Self::lock(&dir)?;
+        if meta_path.try_exists().map_err(|source| WorldSaveError::Io {
+            path: meta_path.clone(),
+            source,
+        })? {
+            return Err(WorldSaveError::AlreadyExists { path: dir });
+        }
+        let world = Self {
+            dir,
+            meta,
+            opened_version: WORLD_META_VERSION,
+            _lock: lock,
+        };
+        for dimension in world.meta.dimensions.iter() {
+            let dimension_dir = world.dimension_dir(dimension.name.as_ref());
+            std::fs::create_dir_all(&dimension_dir).map_err(|source| WorldSaveError::Io {
+                path: dimension_dir,
+                source,
+            })?;
+        }
+        world.write_meta()?;
+        Ok(world)
+    }
+
+    /// Opens an existing world, upgrading its metadata to the current format version if needed.
+    /// The original metadata file of upgraded worlds is kept as `world.ron.v<version>.bak`.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, WorldSaveError> {
+        let dir = dir.into();
+        // Check for the metadata first, so that opening a directory that isn't a world doesn't leave a lock file in it
+        if !dir.join(WORLD_META_FILE).is_file() {
+            return Err(WorldSaveError::NotAWorld { path: dir });
+        }
+        let lock = Self::lock(&dir)?;
+        let (meta, version) = Self::read_meta(&dir)?;
+        let world = Self {
+            dir,
+            meta,
+            opened_version: version,
+            _lock: lock,
+        };
+        if version != WORLD_META_VERSION {
+            let meta_path = world.meta_path();
+            let backup_path = world.dir.join(format!("{WORLD_META_FILE}.v{version}.bak"));
+            std::fs::copy(&meta_path, &backup_path).map_err(|source| WorldSaveError::Io {
+                path: backup_path,
+                source,
+            })?;
+            world.write_meta()?;
+        }
+        world.validate()?;
+        Ok(world)
+    }
+
+    /// Reads and validates the metadata of the world in the given directory without opening it,
+    /// e.g. to list worlds while one of them is open. Returns the metadata and the format version it was stored with.
+    pub fn read_meta(dir: &Path) -> Result<(WorldMeta, u32), WorldSaveError> {
+        let meta_path = dir.join(WORLD_META_FILE);
+        let source = match std::fs::read_to_string(&meta_path) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(WorldSaveError::NotAWorld { path: dir.to_owned() })
+            }
+            result => result.map_err(|source| WorldSaveError::Io {
+                path: meta_path.clone(),
+                source,
+            })?,
+        };
+        let (meta, version) = WorldMeta::from_ron_upgrading(&meta_path, &source)?;
+        meta.validate().map_err(|source| WorldSaveError::Invalid {
+            path: meta_path,
+            source,
+        })?;
+        Ok((meta, version))
+    }
+
+    fn lock(dir: &Path) -> Result<File, WorldSaveError> {
+        let lock_path = dir.join(WORLD_LOCK_FILE);
+        let io_error = |source| WorldSaveError::Io {
+            path: lock_path.clone(),
+            source,
+        };
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(io_error)?;
+        match lock.try_lock() {
+            Ok(()) => Ok(lock),
+            Err(TryLockError::WouldBlock) => Err(WorldSaveError::Locked { path: dir.to_owned() }),
+            Err(TryLockError::Error(e)) => Err(io_error(e)),
+        }
+    }
+
+    /// Checks that the metadata is consistent and the directory of every dimension exists.
+    pub fn validate(&self) -> Result<(), WorldSaveError> {
+        self.meta.validate().map_err(|source| WorldSaveError::Invalid {
+            path: self.meta_path(),
+            source,
+        })?;
+        for dimension in self.meta.dimensions.iter() {
+            let dimension_dir = self.dimension_dir(dimension.name.as_ref());
+            if !dimension_dir.is_dir() {
+                return Err(WorldSaveError::Io {
+                    path: dimension_dir,
+                    source: ErrorKind::NotFound.into(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The world save directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The path of the metadata file.
+    pub fn meta_path(&self) -> PathBuf {
+        self.dir.join(WORLD_META_FILE)
+    }
+
+    /// The world metadata.
+    pub fn meta(&self) -> &WorldMeta {
+        &self.meta
+    }
+
+    /// The format version the metadata was stored with when the world was opened, before any upgrades.
+    pub fn opened_version(&self) -> u32 {
+        self.opened_version
+    }
+
+    /// Replaces the world metadata, and atomically writes it to disk.
+    /// New dimensions get their data directories created.
+    pub fn set_meta(&mut self, meta: WorldMeta) -> Result<(), WorldSaveError> {
+        meta.validate().map_err(|source| WorldSaveError::Invalid {
+            path: self.meta_path(),
+            source,
+        })?;
+        for dimension in meta.dimensions.iter() {
+            let dimension_dir = self.dimension_dir(dimension.name.as_ref());
+            std::fs::create_dir_all(&dimension_dir).map_err(|source| WorldSaveError::Io {
+                path: dimension_dir,
+                source,
+            })?;
+        }
+        self.meta = meta;
+        self.write_meta()
+    }
+
+    /// The data directory of the given dimension.
+    pub fn dimension_dir(&self, dimension: RegistryNameRef) -> PathBuf {
+        self.dir
+            .join(WORLD_DIMENSIONS_DIR)
+            .join(dimension.ns.as_str())
+            .join(dimension.key.as_str())
+    }
+
+    /// Opens the chunk storage of the given dimension.
+    pub fn region_store(&self, dimension: RegistryNameRef) -> Result<RegionStore, RegionError> {
+        RegionStore::open(self.dimension_dir(dimension).join("regions"))
+    }
+
+    /// Writes the metadata to a temporary file, and renames it over the old metadata.
+    fn write_meta(&self) -> Result<(), WorldSaveError> {
+        let meta_path = self.meta_path();
+        let temp_path = self.dir.join(format!("{WORLD_META_FILE}.tmp"));
+        let io_error = |source| WorldSaveError::Io {
+            path: temp_path.clone(),
+            source,
+        };
+        let mut temp = File::create(&temp_path).map_err(io_error)?;
+        temp.write_all(self.meta.to_ron().as_bytes()).map_err(io_error)?;
+        temp.sync_all().map_err(io_error)?;
+        drop(temp);
+        std::fs::rename(&temp_path, &meta_path).map_err(|source| WorldSaveError::Io {
+            path: meta_path.clone(),
+            source,
+        })?;
+        sync_parent_dir(&meta_path).map_err(|source| WorldSaveError::Io {
+            path: self.dir.clone(),
+            source,
+        })
+    }
+}
+
+/// Checks that the name part stays inside of the directory it's joined to, as [`WorldSave::dimension_dir`] does.
+/// Registry names can contain `.` and `/`, so e.g. `..` or a leading `/` would escape the save directory.
+fn is_relative_dir_path(part: &str) -> bool {
+    part.split('/').all(|segment| !matches!(segment, "" | "." | ".."))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::coordinates::AbsChunkPos;
+    use crate::migration::MigrationStep;
+    use crate::registry::RegistryId;
+
+    fn name(name: &str) -> RegistryName {
+        name.parse().unwrap()
+    }
+
+    fn test_meta() -> WorldMeta {
+        let snapshot = RegistrySnapshot::from_entries([
+            (RegistryId::try_from(1).unwrap(), name("air")),
+            (RegistryId::try_from(2).unwrap(), name("stone")),
+        ])
+        .unwrap();
+        let mut meta = WorldMeta::new(1234, snapshot, name("overworld"));
+        meta.dimensions.push(DimensionMeta {
+            name: name("mod:caves"),
+        });
+        meta.spawn.position = AbsBlockPos::new(10, -64, 3);
+        meta.game_time = 24000;
+        meta
+    }
+
+    #[test]
+    fn meta_validation() {
+        let meta = test_meta();
+        assert_eq!(meta.validate(), Ok(()));
+        assert!(meta.dimension(name("mod:caves").as_ref()).is_some());
+        assert!(meta.dimension(name("caves").as_ref()).is_none());
+
+        let (parsed, version) = WorldMeta::from_ron_upgrading(Path::new("world.ron"), &meta.to_ron()).unwrap();
+        assert_eq!(parsed, meta);
+        assert_eq!(version, WORLD_META_VERSION);
+
+        let mut bad = meta.clone();
+        bad.format_version = 0;
+        assert_eq!(bad.validate(), Err(WorldMetaError::WrongVersion(0)));
+        let mut bad = meta.clone();
+        bad.dimensions.clear();
+        assert_eq!(bad.validate(), Err(WorldMetaError::NoDimensions));
+        let mut bad = meta.clone();
+        bad.dimensions.push(DimensionMeta {
+            name: name("overworld"),
+        });
+        assert_eq!(
+            bad.validate(),
+            Err(WorldMetaError::DuplicateDimension(name("overworld")))
+        );
+        for escaping in [
+            "gs:../../x",
+            "gs:/abs",
+            "gs:a//b",
+            "gs:a/",
+            "gs:./a",
+            "..:a",
+            "mod/../..:a",
+        ] {
+            let mut bad = meta.clone();
+            bad.dimensions.push(DimensionMeta { name: name(escaping) });
+            assert_eq!(
+                bad.validate(),
+                Err(WorldMetaError::InvalidDimensionName(name(escaping)))
+            );
+        }
+        let mut nested = meta.clone();
+        nested.dimensions.push(DimensionMeta {
+            name: name("mod.v2:caves/deep..er"),
+        });
+        assert_eq!(nested.validate(), Ok(()));
+        let mut bad = meta;
+        bad.spawn.dimension = name("nether");
+        assert_eq!(
+            bad.validate(),
+            Err(WorldMetaError::UnknownSpawnDimension(name("nether")))
+        );
+    }
+
+    #[test]
+    fn meta_upgrades() {
+        fn rename_time(map: &mut ron::Map) -> Result<(), String> {
+            let key = Value::String("time".to_owned());
+            let time = map.remove(&key).ok_or("missing time")?;
+            map.insert(Value::String("game_time".to_owned()), time);
+            Ok(())
+        }
+        fn add_seed(map: &mut ron::Map) -> Result<(), String> {
+            map.insert(Value::String("seed".to_owned()), Value::Number(7.into()));
+            Ok(())
+        }
+        let migrations = Migrations {
+            steps: &[
+                MigrationStep {
+                    from_version: 1,
+                    description: "Rename time to game_time",
+                    upgrade: rename_time,
+                },
+                MigrationStep {
+                    from_version: 2,
+                    description: "Add the seed",
+                    upgrade: add_seed,
+                },
+            ],
+            ..WORLD_META_MIGRATIONS
+        };
+        let path = Path::new("world.ron");
+        let parse = |source: &str| WorldMeta::from_ron_with_migrations(path, source, &migrations);
+
+        let v1 = r#"(
+            format_version: 1,
+            block_registry: (entries: [((1), "gs:air")]),
+            spawn: (dimension: "gs:overworld", position: ((1, 2, 3))),
+            time: 100,
+            dimensions: [(name: "gs:overworld")],
+        )"#;
+        let (meta, version) = parse(v1).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(meta.format_version, 3);
+        assert_eq!(meta.seed, 7);
+        assert_eq!(meta.game_time, 100);
+        assert_eq!(meta.spawn.position, AbsBlockPos::new(1, 2, 3));
+        assert_eq!(meta.block_registry.len(), 1);
+
+        let v2 = v1
+            .replace("format_version: 1", "format_version: 2")
+            .replace("time:", "game_time:");
+        assert_eq!(parse(&v2).unwrap(), (meta.clone(), 2));
+        let v3 = meta.to_ron();
+        assert_eq!(parse(&v3).unwrap(), (meta, 3));
+
+        assert!(matches!(
+            parse(&v1.replace("time:", "game_time:")),
+            Err(WorldSaveError::Migration {
+                source: MigrationError::StepFailed { from: 1, .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&v1.replace("format_version: 1", "format_version: 4")),
+            Err(WorldSaveError::Migration {
+                source: MigrationError::UnsupportedVersion { version: 4, .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&v1.replace("format_version: 1,", "")),
+            Err(WorldSaveError::Migration {
+                source: MigrationError::MissingVersion { .. },
+                ..
+            })
+        ));
+        assert!(matches!(parse("(format_version: 3"), Err(WorldSaveError::Parse { .. })));
+        assert!(matches!(
+            parse(&v3.replace("seed", "sed")),
+            Err(WorldSaveError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn create_open_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let world_dir = dir.path().join("world");
+        assert!(matches!(
+            WorldSave::open(&world_dir),
+            Err(WorldSaveError::NotAWorld { .. })
+        ));
+        std::fs::create_dir(&world_dir).unwrap();
+        assert!(matches!(
+            WorldSave::open(&world_dir),
+            Err(WorldSaveError::NotAWorld { .. })
+        ));
+        assert!(!world_dir.join(WORLD_LOCK_FILE).exists());
+
+        let world = WorldSave::create(&world_dir, test_meta()).unwrap();
+        assert!(world_dir.join(WORLD_META_FILE).is_file());
+        assert!(world_dir.join("dimensions/mod/caves").is_dir());
+        world.validate().unwrap();
+        // Only one handle to the world can be open at a time
+        assert!(matches!(
+            WorldSave::open(&world_dir),
+            Err(WorldSaveError::Locked { .. })
+        ));
+        assert!(matches!(
+            WorldSave::create(&world_dir, test_meta()),
+            Err(WorldSaveError::Locked { .. })
+        ));
+        // The metadata can still be read
+        assert_eq!(
+            WorldSave::read_meta(&world_dir).unwrap(),
+            (test_meta(), WORLD_META_VERSION)
+        );
+
+        let mut regions = world.region_store(name("overworld").as_ref()).unwrap();
+        regions.save_chunk(AbsChunkPos::ZERO, &Chunk::new()).unwrap();
+        drop(regions);
+        drop(world);
+
+        assert!(matches!(
+            WorldSave::create(&world_dir, test_meta()),
+            Err(WorldSaveError::AlreadyExists { .. })
+        ));
+        let mut world = WorldSave::open(&world_dir).unwrap();
+        assert_eq!(world.meta(), &test_meta());
+        let mut regions = world.region_store(name("overworld").as_ref()).unwrap();
+        assert!(regions.load_chunk(AbsChunkPos::ZERO).unwrap() == Some(Chunk::new()));
+
+        let mut meta = test_meta();
+        meta.game_time += 1;
+        meta.dimensions.push(DimensionMeta { name: name("end") });
+        world.set_meta(meta.clone()).unwrap();
+        assert!(world_dir.join("dimensions/gs/end").is_dir());
+        let mut escaping = meta.clone();
+        escaping.dimensions.push(DimensionMeta {
+            name: name("gs:../../escaped"),
+        });
+        assert!(matches!(world.set_meta(escaping), Err(WorldSaveError::Invalid { .. })));
+        assert!(!dir.path().join("escaped").exists());
+        meta.dimensions.clear();
+        assert!(matches!(world.set_meta(meta), Err(WorldSaveError::Invalid { .. })));
+        drop(world);
+        assert_eq!(WorldSave::open(&world_dir).unwrap().meta().game_time, 24001);
+
+        std::fs::remove_dir(world_dir.join("dimensions/gs/end")).unwrap();
+        assert!(matches!(WorldSave::open(&world_dir), Err(WorldSaveError::Io { .. })));
+        std::fs::write(world_dir.join(WORLD_META_FILE), "(format_version: 99)").unwrap();
+        assert!(matches!(
+            WorldSave::open(&world_dir),
+            Err(WorldSaveError::Migration {
+                source: MigrationError::UnsupportedVersion { version: 99, .. },
+                ..
+            })
+        ));
+    }
+}